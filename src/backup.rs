@@ -1,19 +1,31 @@
-use crate::utility::compression::{compress_files_to_tar, compress_folder_to_tar};
+use crate::utility::compression::{compress_files_to_tar, compress_folder_to_tar, strip_archive_extension};
+use crate::utility::configs::backup_config::BackupConfig;
 use crate::utility::configs::retention_policy::RetentionPolicy;
-use crate::utility::configs::server_config::ServerConfig;
-use crate::utility::docker::{start_containers, stop_containers};
-use crate::utility::server::Server;
+use crate::utility::configs::retry_policy::RetryPolicy;
+use crate::utility::configs::destination_config::DestinationConfig;
+use crate::utility::container_guard::ContainerGuard;
+use crate::utility::configs::hook_config::{HookFailurePolicy, HookStage};
+use crate::utility::configs::volume_discovery_config::VolumeDiscovery;
+use crate::utility::docker::{exec_in_container, get_container_labels, inspect_volume, list_volumes, map_containers_to_volumes, ConsistencyMode};
+use crate::utility::storage::{StorageBackend, PARTIAL_SUFFIX};
 use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Utc};
 use cron::Schedule;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
 use std::fs;
 use std::ops::Sub;
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use rand::Rng;
 use tokio::time::sleep;
 
+/// The directory where the volumes to be backed up are mounted.
+const BACKUP_PATH: &str = "/backup";
+
 /// Configures and manages a scheduled backup process based on a cron expression.
 ///
 /// This function continuously checks for the next scheduled backup time as defined
@@ -22,10 +34,14 @@ use tokio::time::sleep;
 /// old backups based on the retention policy provided in `retention_config`. It then logs
 /// the time of the next backup and waits until that time is reached.
 ///
+/// A failed backup is logged and does not stop the schedule.
+///
 /// # Arguments
 ///
-/// * `server_config` - A reference to a `ServerConfig` containing connection information for the server.
+/// * `destinations` - The destinations where backups are stored.
 /// * `retention_config` - A reference to a `RetentionConfig` that defines the retention policy for old backups.
+/// * `retry_policy` - A reference to a `RetryPolicy` that defines how failed operations on the destination are retried.
+/// * `backup_config` - A reference to a `BackupConfig` that defines how the volumes are backed up.
 /// * `backup_cron` - A cron expression that defines the schedule for the backups.
 /// * `temp_path` - The local path where temporary backup files will be stored.
 ///
@@ -33,8 +49,10 @@ use tokio::time::sleep;
 ///
 /// * `Result<(), Box<dyn Error>>` - Returns an empty result if the operation is successful.
 ///   Otherwise, it returns an error wrapped in a `Box<dyn Error>`.
-pub async fn configure_cron_scheduled_backup(server_config: &ServerConfig,
+pub async fn configure_cron_scheduled_backup(destinations: &[DestinationConfig],
                                              retention_config: &RetentionPolicy,
+                                             retry_policy: &RetryPolicy,
+                                             backup_config: &BackupConfig,
                                              backup_cron: &str,
                                              temp_path: &str) -> Result<(), Box<dyn Error>> {
     let schedule = Schedule::from_str(backup_cron)?;
@@ -53,71 +71,470 @@ pub async fn configure_cron_scheduled_backup(server_config: &ServerConfig,
             let duration = next_time - now;
             sleep(std::time::Duration::from_secs(duration.num_seconds() as u64)).await;
 
-            run_backup(server_config, retention_config, temp_path)?;
+            if let Err(e) = run_backup(destinations, retention_config, retry_policy, backup_config, temp_path) {
+                println!("Backup failed: {}", e);
+            }
         }
     }
 }
 
-/// Performs a backup operation by compressing Docker volumes (folders in the "/backup" directory)
-/// and uploading them to a remote server. Afterward, the function removes old backups according
-/// to the retention policy provided in `retention_config`.
+/// Performs a backup operation by compressing Docker volumes (folders in the "/backup" directory, or the
+/// volumes discovered through the Docker Engine API) and uploading them to all configured destinations. Afterward, the function removes old backups from
+/// each destination according to the retention policy provided in `retention_config`.
 ///
 /// This function stops the containers associated with each volume, compresses the volume's
-/// data into a tar.gz archive, and then restarts the containers. It then combines all
-/// individual volume backups into a single archive, which is uploaded to the specified
-/// server.
+/// data into an archive, and then restarts the containers. Volumes used by the same container are
+/// backed up together, so that the container can be stopped only once if `backup_config.stop_containers_once`
+/// is set, and up to `backup_config.concurrency` of these groups are backed up at the same time. It then combines all
+/// individual volume backups into a single archive, which is uploaded to all destinations in parallel.
 ///
 /// After the upload, the function removes temporary backup files and runs the `remove_old_backups`
-/// function to ensure old backups are deleted based on the specified retention policy.
+/// function on each destination to ensure old backups are deleted based on the specified retention policy.
+/// A failing destination does not prevent the backup from being stored in the other destinations.
 ///
 /// # Arguments
 ///
-/// * `server_config` - A reference to a `ServerConfig` containing connection information for the server.
+/// * `destinations` - The destinations where backups are stored.
 /// * `retention_config` - A reference to a `RetentionConfig` that defines how many backups to retain.
+/// * `retry_policy` - A reference to a `RetryPolicy` that defines how failed operations on the destination are retried.
+/// * `backup_config` - A reference to a `BackupConfig` that defines how the volumes are backed up.
 /// * `temp_path` - The local path where temporary backup files will be stored.
 ///
 /// # Returns
 ///
 /// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error if something goes wrong.
-pub fn run_backup(server_config: &ServerConfig, retention_config: &RetentionPolicy, temp_path: &str) -> Result<(), Box<dyn Error>> {
-    const BACKUP_PATH: &str = "/backup";
-
+///   If some volumes could not be archived, the error lists the failures of all groups. If the backup
+///   could not be stored in some of the destinations, the error lists them.
+pub fn run_backup(destinations: &[DestinationConfig],
+                  retention_config: &RetentionPolicy,
+                  retry_policy: &RetryPolicy,
+                  backup_config: &BackupConfig,
+                  temp_path: &str) -> Result<(), Box<dyn Error>> {
     // Create the temp directory if it doesn't exist
     if !Path::new(temp_path).exists() { fs::create_dir_all(temp_path)?; }
 
-    let mut archives_paths: Vec<String> = Vec::new();
-
-    let volume_names = get_volume_dirs(BACKUP_PATH)?;
-
-    // Compress each volume directory into a tar.gz archive
-    for volume in &volume_names {
-        let backup_archive_path = format!("{}/{}.tar.gz", temp_path, volume);
-        let volume_path = format!("{}/{}", BACKUP_PATH, volume);
-        archives_paths.push(backup_archive_path.clone());
+    let volume_paths = find_volumes(backup_config)?;
+    let volume_names: Vec<String> = volume_paths.keys().cloned().collect();
+    let container_volumes = map_containers_to_volumes(&backup_config.docker, &volume_names)?;
+    let volume_groups = group_volumes_by_containers(&volume_names, &container_volumes);
+
+    // Compress the volumes of each group into archives, backing up several groups at the same time
+    let next_group = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<Vec<String>, String>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..backup_config.concurrency.min(volume_groups.len()))
+            .map(|_| scope.spawn(|| {
+                let mut results = Vec::new();
+                while let Some(group) = volume_groups.get(next_group.fetch_add(1, Ordering::SeqCst)) {
+                    // A panic only fails its own group, the results of the other groups are kept
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        backup_volume_group(group, &volume_paths, backup_config, temp_path).map_err(|e| e.to_string())
+                    })).unwrap_or_else(|_| Err(format!("The backup of the volumes {:?} panicked.", group.volumes)));
+                    results.push((group.index, result));
+                }
+                results
+            }))
+            .collect();
+
+        workers.into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|_| vec![(0, Err("A backup thread panicked.".to_string()))]))
+            .collect()
+    });
 
-        let container_ids = stop_containers(volume)?;
-        let result = compress_folder_to_tar(&volume_path, &backup_archive_path);
-        start_containers(container_ids)?;
-        result?
+    let mut archives_paths: Vec<String> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+    results.sort_by_key(|(index, _)| *index);
+    for (_, result) in results {
+        match result {
+            Ok(paths) => archives_paths.extend(paths),
+            Err(e) => errors.push(e),
+        }
+    }
+    if !errors.is_empty() {
+        fs::remove_dir_all(temp_path)?;
+        return Err(format!("Backup failed: {}", errors.join(" ")).into());
     }
 
     // Combine all volume archives into a single backup file with a timestamp
     let now = Local::now();
     let timestamp = now.format("%Y-%m-%dT%H-%M-%S").to_string();
-    let combined_backup_name = format!("backup-{}.tar.gz", timestamp);
+    let combined_backup_name = format!("backup-{}.{}", timestamp, backup_config.compression.codec.extension());
     let combined_backup_archive_path = format!("{}/{}", temp_path, combined_backup_name);
-    let server_combined_backup_path = format!("{}/{}", server_config.server_directory, combined_backup_name);
-    compress_files_to_tar(&archives_paths, &combined_backup_archive_path)?;
-
-    // Upload backup to server and delete temporary files
-    Server::new(server_config.clone()).upload_file(&server_combined_backup_path,
-                                                   &combined_backup_archive_path)?;
+    compress_files_to_tar(&archives_paths, &combined_backup_archive_path, &backup_config.compression)?;
+
+    // Upload backup to all destinations in parallel and delete temporary files
+    let results: Vec<(&DestinationConfig, Result<String, String>)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = destinations.iter()
+            .map(|destination| (destination, scope.spawn(|| {
+                store_backup(destination, retention_config, retry_policy,
+                             &combined_backup_name, &combined_backup_archive_path)
+                    .map_err(|e| e.to_string())
+            })))
+            .collect();
+
+        handles.into_iter()
+            .map(|(destination, handle)| (destination, handle.join()
+                .unwrap_or_else(|_| Err("The upload thread panicked.".to_string()))))
+            .collect()
+    });
     fs::remove_dir_all(temp_path)?;
 
-    remove_old_backups(server_config, retention_config)?;
+    let mut failed_destinations = Vec::new();
+    for (destination, result) in &results {
+        match result {
+            Ok(location) => println!("The {:?} volumes have been backed up to the {} (destination '{}')",
+                                     volume_names, location, destination.name),
+            Err(e) => {
+                println!("Backup to the destination '{}' failed: {}", destination.name, e);
+                failed_destinations.push(destination.name.clone());
+            }
+        }
+    }
+
+    if failed_destinations.len() == results.len() {
+        return Err(format!("Backup failed for all destinations: {:?}", failed_destinations).into());
+    }
+    if !failed_destinations.is_empty() {
+        return Err(format!("Backup completed partially. It failed for the destinations: {:?}", failed_destinations).into());
+    }
+
+    println!("Backup completed successfully.");
+    Ok(())
+}
+
+/// Uploads a backup archive to a single destination, verifies the upload and removes old backups
+/// from the destination according to the retention policy.
+///
+/// # Arguments
+///
+/// * `destination` - The destination where the backup is stored.
+/// * `retention_config` - A reference to a `RetentionConfig` that defines how many backups to retain.
+/// * `retry_policy` - A reference to a `RetryPolicy` that defines how failed operations on the destination are retried.
+/// * `file_name` - The name of the backup file in the destination.
+/// * `local_file_path` - The path of the local backup archive.
+///
+/// # Returns
+///
+/// * `Result<String, Box<dyn Error>>` - The location of the stored backup, or an error if something goes wrong.
+fn store_backup(destination: &DestinationConfig,
+                retention_config: &RetentionPolicy,
+                retry_policy: &RetryPolicy,
+                file_name: &str,
+                local_file_path: &str) -> Result<String, Box<dyn Error>> {
+    let storage = destination.storage.create_backend(retry_policy);
+    storage.upload_file(file_name, local_file_path)?;
+    verify_upload(storage.as_ref(), file_name, local_file_path)?;
+    remove_old_backups(storage.as_ref(), retention_config)?;
+
+    Ok(storage.location(file_name))
+}
+
+/// A set of volumes that share containers and are therefore backed up together.
+///
+/// - `index`: The position of the group, used for keeping the order of the volumes in the backup.
+/// - `volumes`: The names of the volumes in the group, ordered so that the volumes of each container follow each other.
+/// - `containers`: The volumes of the group used by each running container.
+struct VolumeGroup {
+    index: usize,
+    volumes: Vec<String>,
+    containers: BTreeMap<String, Vec<String>>,
+}
+
+impl VolumeGroup {
+    /// Returns the IDs of the containers of the group using the given volume.
+    fn containers_using(&self, volume: &str) -> Vec<String> {
+        self.containers.iter()
+            .filter(|(_, volumes)| volumes.iter().any(|used| used == volume))
+            .map(|(container_id, _)| container_id.clone())
+            .collect()
+    }
+
+    /// Returns the strongest consistency mode of the volumes of the group used by a container.
+    fn consistency_mode_of(&self, container_id: &str, backup_config: &BackupConfig) -> ConsistencyMode {
+        self.containers[container_id].iter()
+            .map(|volume| backup_config.consistency_mode_for(volume))
+            .max()
+            .unwrap_or(backup_config.consistency_mode)
+    }
+}
+
+/// Groups volumes so that all volumes used by the same container end up in the same group.
+///
+/// Each volume starts in its own group, and the groups of all volumes used by a container are merged.
+/// Volumes that share no containers can therefore be backed up independently.
+///
+/// # Arguments
+///
+/// * `volumes` - The names of the volumes to be backed up.
+/// * `container_volumes` - The volumes used by each running container.
+///
+/// # Returns
+///
+/// * `Vec<VolumeGroup>` - The groups of volumes.
+fn group_volumes_by_containers(volumes: &[String], container_volumes: &BTreeMap<String, Vec<String>>) -> Vec<VolumeGroup> {
+    let mut groups: Vec<VolumeGroup> = volumes.iter()
+        .map(|volume| VolumeGroup { index: 0, volumes: vec![volume.clone()], containers: BTreeMap::new() })
+        .collect();
+
+    for (container_id, used_volumes) in container_volumes {
+        let (shared, mut separate): (Vec<VolumeGroup>, Vec<VolumeGroup>) = groups.into_iter()
+            .partition(|group| group.volumes.iter().any(|volume| used_volumes.contains(volume)));
+
+        let mut merged = VolumeGroup { index: 0, volumes: Vec::new(), containers: BTreeMap::new() };
+        for group in shared {
+            merged.volumes.extend(group.volumes);
+            merged.containers.extend(group.containers);
+        }
+        merged.containers.insert(container_id.clone(), used_volumes.clone());
+
+        separate.push(merged);
+        groups = separate;
+    }
+
+    for (index, group) in groups.iter_mut().enumerate() {
+        group.index = index;
+
+        // Keep the volumes of each container together, so that it is stopped for as short as possible
+        let mut ordered_volumes: Vec<String> = Vec::new();
+        for volume in group.containers.values().flatten().chain(group.volumes.iter()) {
+            if !ordered_volumes.contains(volume) { ordered_volumes.push(volume.clone()); }
+        }
+        group.volumes = ordered_volumes;
+    }
+
+    groups
+}
+
+/// Backs up a group of volumes by stopping their containers, compressing each volume into an
+/// archive and restarting the containers.
+///
+/// If `backup_config.stop_containers_once` is set, every container is stopped right before its first
+/// volume is compressed and restarted right after its last one, so it is restarted only once and
+/// stays down no longer than needed. Otherwise, the containers of each volume are stopped and
+/// restarted separately for each volume.
+///
+/// Depending on the consistency mode of the volumes, containers are stopped, paused or left running.
+/// A container using volumes with different modes is suspended with the strongest of them, unless
+/// its labels set its mode.
+///
+/// The suspended containers are held by a `ContainerGuard`, so they are restarted even if
+/// compressing a volume fails or panics. The pre-backup hooks of every container run right before
+/// it is stopped, and its post-backup hooks right after it is restarted. The post-backup hooks of
+/// a container whose pre-backup hooks ran also run if the backup fails, after it is restarted.
+///
+/// # Arguments
+///
+/// * `group` - The group of volumes to be backed up.
+/// * `volume_paths` - The paths of the data of the volumes, by volume name.
+/// * `backup_config` - A reference to a `BackupConfig` that defines how the volumes are backed up.
+/// * `temp_path` - The local path where the archives will be stored.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>, Box<dyn Error>>` - The paths of the archives, or an error listing everything that
+///   went wrong.
+fn backup_volume_group(group: &VolumeGroup,
+                       volume_paths: &BTreeMap<String, String>,
+                       backup_config: &BackupConfig,
+                       temp_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut guard = ContainerGuard::new(&backup_config.docker);
+    let mut hooked_containers = Vec::new();
+    let result = archive_volume_group(group, volume_paths, backup_config, temp_path, &mut guard, &mut hooked_containers);
+
+    // Whatever failed, restart the containers and run the post-backup hooks that are still due
+    let mut errors = Vec::new();
+    if let Err(e) = &result { errors.push(e.to_string()); }
+    if let Err(e) = guard.resume_all() { errors.push(e.to_string()); }
+    if let Err(e) = run_post_hooks(backup_config, &guard, &mut hooked_containers) { errors.push(e.to_string()); }
+
+    match result {
+        Ok(archives_paths) if errors.is_empty() => Ok(archives_paths),
+        _ => Err(errors.join(" ").into()),
+    }
+}
+
+/// Compresses the volumes of a group into archives, suspending and resuming their containers and
+/// running their hooks, as described in `backup_volume_group`.
+///
+/// Returns at the first error, possibly leaving containers suspended in the guard and post-backup hooks due.
+///
+/// # Arguments
+///
+/// * `group` - The group of volumes to be backed up.
+/// * `volume_paths` - The paths of the data of the volumes, by volume name.
+/// * `backup_config` - A reference to a `BackupConfig` that defines how the volumes are backed up.
+/// * `temp_path` - The local path where the archives will be stored.
+/// * `guard` - The guard holding the suspended containers.
+/// * `hooked_containers` - The containers whose pre-backup hooks ran and whose post-backup hooks are still due,
+///   with the volumes their hooks are for. Containers are added before their pre-backup hooks run.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>, Box<dyn Error>>` - The paths of the archives, or an error if something goes wrong.
+fn archive_volume_group(group: &VolumeGroup,
+                        volume_paths: &BTreeMap<String, String>,
+                        backup_config: &BackupConfig,
+                        temp_path: &str,
+                        guard: &mut ContainerGuard,
+                        hooked_containers: &mut Vec<(String, Vec<String>)>) -> Result<Vec<String>, Box<dyn Error>> {
+    let extension = backup_config.compression.codec.extension();
+    let compress_volume = |volume: &str| -> Result<String, Box<dyn Error>> {
+        let backup_archive_path = format!("{}/{}.{}", temp_path, volume, extension);
+        compress_folder_to_tar(&volume_paths[volume], &backup_archive_path, &backup_config.compression)?;
+        Ok(backup_archive_path)
+    };
+
+    let mut archives_paths = Vec::new();
+
+    if !backup_config.stop_containers_once {
+        for volume in &group.volumes {
+            let container_ids = group.containers_using(volume);
+            hooked_containers.extend(container_ids.iter().map(|container_id| (container_id.clone(), vec![volume.clone()])));
+            run_hooks(backup_config, &container_ids, |_| std::slice::from_ref(volume), HookStage::Pre)?;
+            guard.suspend(&container_ids, |_| backup_config.consistency_mode_for(volume))?;
+            let result = compress_volume(volume);
+            guard.resume(&container_ids)?;
+            let hooks_result = run_post_hooks(backup_config, guard, hooked_containers);
+            archives_paths.push(result?);
+            hooks_result?;
+        }
+        return Ok(archives_paths);
+    }
+
+    for (position, volume) in group.volumes.iter().enumerate() {
+        let new_container_ids: Vec<String> = group.containers_using(volume).into_iter()
+            .filter(|container_id| !guard.contains(container_id))
+            .collect();
+        hooked_containers.extend(new_container_ids.iter()
+            .map(|container_id| (container_id.clone(), group.containers[container_id].clone())));
+        run_hooks(backup_config, &new_container_ids, |container_id| group.containers[container_id].as_slice(), HookStage::Pre)?;
+        guard.suspend(&new_container_ids,
+                      |container_id| group.consistency_mode_of(container_id, backup_config))?;
+
+        let result = compress_volume(volume);
+
+        // Restart the containers whose volumes are all backed up, or all of them if the backup failed
+        let remaining_volumes = &group.volumes[position + 1..];
+        let finished: Vec<String> = guard.container_ids().into_iter()
+            .filter(|container_id| result.is_err()
+                || !group.containers[container_id].iter().any(|used| remaining_volumes.contains(used)))
+            .collect();
+        guard.resume(&finished)?;
+
+        let hooks_result = run_post_hooks(backup_config, guard, hooked_containers);
+        archives_paths.push(result?);
+        hooks_result?;
+    }
+
+    Ok(archives_paths)
+}
+
+/// Runs the post-backup hooks of the hooked containers that are no longer suspended, and forgets them.
+///
+/// # Arguments
+///
+/// * `backup_config` - A reference to a `BackupConfig` that defines the hooks.
+/// * `guard` - The guard holding the suspended containers.
+/// * `hooked_containers` - The containers whose post-backup hooks are due, with the volumes their hooks are for.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error listing the failed hooks.
+fn run_post_hooks(backup_config: &BackupConfig,
+                  guard: &ContainerGuard,
+                  hooked_containers: &mut Vec<(String, Vec<String>)>) -> Result<(), Box<dyn Error>> {
+    let (resumed, still_suspended): (Vec<_>, Vec<_>) = std::mem::take(hooked_containers).into_iter()
+        .partition(|(container_id, _)| !guard.contains(container_id));
+    *hooked_containers = still_suspended;
+
+    let container_ids: Vec<String> = resumed.iter().map(|(container_id, _)| container_id.clone()).collect();
+    let volumes_of = |container_id: &str| resumed.iter()
+        .find(|(id, _)| id == container_id)
+        .map_or(&[][..], |(_, volumes)| volumes.as_slice());
+    run_hooks(backup_config, &container_ids, volumes_of, HookStage::Post)
+}
+
+/// Runs the hooks of a stage inside containers.
+///
+/// The hooks of a container are the command in its `backup.exec-pre` or `backup.exec-post` label, followed by
+/// the global hook and the hooks of the given volumes. Failing hooks are logged if the failure policy is `warn`,
+/// and so are containers whose labels cannot be read, which then only run the global and volume hooks.
+///
+/// # Arguments
+///
+/// * `backup_config` - A reference to a `BackupConfig` that defines the hooks.
+/// * `container_ids` - The IDs of the containers to run the hooks in.
+/// * `volumes_of` - Returns the backed-up volumes of a container whose hooks run.
+/// * `stage` - Whether the hooks run before stopping or after restarting the containers.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error listing the failed hooks if the
+///   failure policy is `abort`.
+fn run_hooks<'a>(backup_config: &BackupConfig,
+                 container_ids: &[String],
+                 volumes_of: impl Fn(&str) -> &'a [String],
+                 stage: HookStage) -> Result<(), Box<dyn Error>> {
+    let hooks = &backup_config.hooks;
+    let mut errors = Vec::new();
+
+    for container_id in container_ids {
+        // Without the labels, only the configured hooks can run
+        let labels = match get_container_labels(&backup_config.docker, container_id) {
+            Ok(labels) => labels,
+            Err(e) => match hooks.failure_policy {
+                HookFailurePolicy::Warn => {
+                    println!("Warning: Failed to read the hook labels of container {}: {}", container_id, e);
+                    HashMap::new()
+                }
+                HookFailurePolicy::Abort => {
+                    errors.push(e.to_string());
+                    continue;
+                }
+            },
+        };
+        let mut commands: Vec<String> = labels.get(stage.label()).cloned()
+            .filter(|command| !command.trim().is_empty())
+            .into_iter()
+            .collect();
+        for command in hooks.commands_for(stage, volumes_of(container_id)) {
+            if !commands.contains(&command) { commands.push(command); }
+        }
+
+        for command in commands {
+            let short_id = &container_id[..container_id.len().min(12)];
+            println!("Running {} hook '{}' in container {}", stage.name(), command, short_id);
+            if let Err(e) = exec_in_container(&backup_config.docker, container_id, &command, hooks.timeout()) {
+                match hooks.failure_policy {
+                    HookFailurePolicy::Warn => println!("Warning: {}", e),
+                    HookFailurePolicy::Abort => errors.push(e.to_string()),
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(format!("The {} hooks failed: {}", stage.name(), errors.join(" ")).into()) }
+}
+
+/// Verifies that a file uploaded to the destination has the same size as the local file.
+///
+/// # Arguments
+///
+/// * `storage` - The storage backend the file was uploaded to.
+/// * `file_name` - The name of the uploaded file in the destination.
+/// * `local_file_path` - The path of the local file that was uploaded.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - An empty result if the sizes match, or an error if they differ or something goes wrong.
+fn verify_upload(storage: &dyn StorageBackend, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>> {
+    let local_size = fs::metadata(local_file_path)?.len();
+    let remote_size = storage.stat_file(file_name)?.size;
+
+    if local_size != remote_size {
+        return Err(format!("Upload of {} is incomplete: expected {} bytes, found {} bytes.",
+                           file_name, local_size, remote_size).into());
+    }
 
-    println!("Backup completed successfully. The {:?} volumes have been backed up to the {}",
-             volume_names, server_combined_backup_path);
     Ok(())
 }
 
@@ -141,15 +558,76 @@ fn get_volume_dirs(backup_folder_path: &str) -> Result<Vec<String>, Box<dyn Erro
         .collect())
 }
 
-/// Removes old backups from the server based on the retention policy.
+/// Finds the volumes to be backed up and the paths where their data can be read.
 ///
-/// This function connects to the server using the provided configuration,
-/// retrieves the list of backup files, and determines which backups to delete
-/// according to the retention policy.
+/// Depending on `backup_config.volume_discovery`, the volumes are either the folders mounted in `/backup`,
+/// or the volumes known to the Docker daemon that are selected by label or name. The data of the latter is
+/// read from the host's Docker volumes directory, which must be mounted into this container.
 ///
 /// # Arguments
 ///
-/// * `server_config` - A reference to a `ServerConfig` struct containing the server's configuration.
+/// * `backup_config` - A reference to a `BackupConfig` that defines how the volumes are found.
+///
+/// # Returns
+///
+/// * `Result<BTreeMap<String, String>, Box<dyn Error>>` - The paths of the data of the volumes by volume name,
+///   or an error if something goes wrong.
+pub fn find_volumes(backup_config: &BackupConfig) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let discovery = &backup_config.volume_discovery;
+    if discovery.discovery == VolumeDiscovery::Mounts {
+        return Ok(get_volume_dirs(BACKUP_PATH)?.into_iter()
+            .map(|volume| (volume.clone(), format!("{}/{}", BACKUP_PATH, volume)))
+            .collect());
+    }
+
+    let mut volume_paths = BTreeMap::new();
+    for volume in list_volumes(&backup_config.docker)? {
+        if !discovery.selects(&volume.name, &volume.labels) { continue; }
+
+        let data_path = discovery.data_path(&volume.mountpoint);
+        if !Path::new(&data_path).is_dir() {
+            return Err(format!("The data of volume {} is not accessible at {}. Mount the host's Docker volumes directory at {}.",
+                               volume.name, data_path, discovery.volumes_path).into());
+        }
+        volume_paths.insert(volume.name, data_path);
+    }
+    Ok(volume_paths)
+}
+
+/// Returns the path where the data of a volume can be read and written.
+///
+/// # Arguments
+///
+/// * `backup_config` - A reference to a `BackupConfig` that defines how the volumes are found.
+/// * `volume` - The name of the volume.
+///
+/// # Returns
+///
+/// * `Result<String, Box<dyn Error>>` - The path of the volume's data, or an error if the volume does not exist.
+pub fn get_volume_path(backup_config: &BackupConfig, volume: &str) -> Result<String, Box<dyn Error>> {
+    let path = match backup_config.volume_discovery.discovery {
+        VolumeDiscovery::Mounts => format!("{}/{}", BACKUP_PATH, volume),
+        VolumeDiscovery::Docker => {
+            let mountpoint = inspect_volume(&backup_config.docker, volume)?.mountpoint;
+            backup_config.volume_discovery.data_path(&mountpoint)
+        }
+    };
+
+    if !Path::new(&path).exists() {
+        return Err(format!("Volume {} does not exist.", volume).into());
+    }
+    Ok(path)
+}
+
+/// Removes old backups from the destination based on the retention policy.
+///
+/// This function retrieves the list of backup files from the destination,
+/// and determines which backups to delete according to the retention policy.
+/// Abandoned partial uploads are removed as well.
+///
+/// # Arguments
+///
+/// * `storage` - The storage backend holding the backups.
 /// * `retention_config` - A reference to a `RetentionPolicy` struct defining the backup retention rules.
 ///
 /// # Returns
@@ -158,23 +636,56 @@ fn get_volume_dirs(backup_folder_path: &str) -> Result<Vec<String>, Box<dyn Erro
 ///
 /// # Errors
 ///
-/// This function returns errors that might occur while listing or deleting files from the server.
+/// This function returns errors that might occur while listing or deleting files from the destination.
 pub fn remove_old_backups(
-    server_config: &ServerConfig,
+    storage: &dyn StorageBackend,
     retention_config: &RetentionPolicy,
 ) -> Result<(), Box<dyn Error>> {
-    let server = Server::new(server_config.clone());
+    // Fetch the list of files from the destination
+    let file_names = storage.list_files()?;
+    remove_stale_partial_uploads(storage, &file_names)?;
 
-    // Fetch the list of backup files from the server
-    let backup_names = server.list_files()?.into_iter().filter(|file_name|
-        file_name.starts_with("backup-") && file_name.ends_with(".tar.gz")).collect();
+    // Only complete backups are subject to the retention policy
+    let backup_names = file_names.into_iter().filter(|file_name|
+        file_name.starts_with("backup-") && strip_archive_extension(file_name).is_some()).collect();
 
     // Determine which backups to delete based on the retention policy
     let backups_to_delete = filter_backups_to_delete(backup_names, retention_config);
 
     // Delete old backups that are not retained
     for file_name in backups_to_delete {
-        server.delete_file(&file_name)?;
+        storage.delete_file(&file_name)?;
+    }
+
+    Ok(())
+}
+
+/// Removes partial uploads that were abandoned by an interrupted backup.
+///
+/// A partial upload is considered abandoned once the timestamp in its name is older than
+/// `STALE_PARTIAL_UPLOAD_AGE_IN_HOURS`, so uploads of a backup running concurrently are left alone.
+///
+/// # Arguments
+///
+/// * `storage` - The storage backend holding the backups.
+/// * `file_names` - The names of all files in the destination.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - Returns `Ok(())` on success, or an `Error` if a file cannot be deleted.
+fn remove_stale_partial_uploads(storage: &dyn StorageBackend, file_names: &[String]) -> Result<(), Box<dyn Error>> {
+    const STALE_PARTIAL_UPLOAD_AGE_IN_HOURS: i64 = 24;
+    let now = Utc::now();
+
+    for file_name in file_names {
+        let backup_date = file_name.strip_suffix(PARTIAL_SUFFIX).and_then(parse_backup_date);
+
+        if let Some(date) = backup_date {
+            if now - date > Duration::hours(STALE_PARTIAL_UPLOAD_AGE_IN_HOURS) {
+                println!("Removing stale partial upload {}", file_name);
+                storage.delete_file(file_name)?;
+            }
+        }
     }
 
     Ok(())
@@ -226,7 +737,7 @@ fn filter_backups_to_delete(backups: Vec<String>, retention: &RetentionPolicy) -
     backups_with_dates.retain(|(_, date)| date > &(now - retention_period));
 
     // Sort backups by date in descending order (newest first)
-    backups_with_dates.sort_by(|a, b| b.1.cmp(&a.1));
+    backups_with_dates.sort_by_key(|(_, date)| std::cmp::Reverse(*date));
 
 
     if backups_with_dates.is_empty() { return backups; }
@@ -260,9 +771,10 @@ fn filter_backups_to_delete(backups: Vec<String>, retention: &RetentionPolicy) -
 
 /// Parses a backup file name to extract the date and time it was created.
 ///
-/// The file name should start with "backup-" and end with ".tar.gz". The date and time
-/// should be in the format "YYYY-MM-DDTHH-MM-SS". If the file name does not conform to
-/// this format, `None` is returned.
+/// The file name should start with "backup-" and end with the archive extension of any codec,
+/// e.g. ".tar.gz" or ".tar.zst". The date and time should be in the format "YYYY-MM-DDTHH-MM-SS".
+/// Backups are named after the local time they were created at, so the timestamp is interpreted in the
+/// local timezone and converted to UTC. If the file name does not conform to this format, `None` is returned.
 ///
 /// # Arguments
 ///
@@ -272,18 +784,154 @@ fn filter_backups_to_delete(backups: Vec<String>, retention: &RetentionPolicy) -
 ///
 /// * `Option<DateTime<Utc>>` - Returns `Some(DateTime<Utc>)` if parsing is successful,
 ///   or `None` if the file name does not match the expected format.
-fn parse_backup_date(backup: &str) -> Option<DateTime<Utc>> {
-    let prefix = "backup-";
-    let suffix = ".tar.gz";
+pub fn parse_backup_date(backup: &str) -> Option<DateTime<Utc>> {
+    let datetime_str = strip_archive_extension(backup)?.strip_prefix("backup-")?;
+    if let Ok(naive_dt) = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%dT%H-%M-%S") {
+        // A time that occurs twice when the clocks go back resolves to its first occurrence
+        return Local.from_local_datetime(&naive_dt).earliest().map(|date| date.with_timezone(&Utc));
+    }
 
-    if !backup.starts_with(prefix) || !backup.ends_with(suffix) {
-        return None;
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::storage::{partial_file_name, FileStat};
+    use std::cell::RefCell;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
     }
 
-    let datetime_str = &backup[prefix.len()..backup.len() - suffix.len()];
-    if let Ok(naive_dt) = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%dT%H-%M-%S") {
-        return Some(Utc.from_utc_datetime(&naive_dt));
+    #[test]
+    fn group_volumes_by_containers_merges_volumes_of_shared_containers() {
+        let volumes = names(&["app-data", "db-data", "cache-data", "logs", "unused"]);
+        let container_volumes = BTreeMap::from([
+            ("app".to_string(), names(&["app-data", "db-data"])),
+            ("db".to_string(), names(&["db-data"])),
+            ("worker".to_string(), names(&["cache-data", "app-data"])),
+            ("shipper".to_string(), names(&["logs"])),
+        ]);
+
+        let groups = group_volumes_by_containers(&volumes, &container_volumes);
+        let summary: Vec<(Vec<String>, Vec<String>)> = groups.iter()
+            .map(|group| (group.volumes.clone(), group.containers.keys().cloned().collect()))
+            .collect();
+
+        assert_eq!(summary, vec![
+            (names(&["unused"]), names(&[])),
+            (names(&["logs"]), names(&["shipper"])),
+            (names(&["app-data", "db-data", "cache-data"]), names(&["app", "db", "worker"])),
+        ]);
+        assert_eq!(groups.iter().map(|group| group.index).collect::<Vec<_>>(), vec![0, 1, 2]);
     }
 
-    None
+    #[test]
+    fn group_volumes_by_containers_keeps_disjoint_volumes_apart() {
+        let volumes = names(&["a", "b"]);
+        let container_volumes = BTreeMap::from([
+            ("first".to_string(), names(&["a"])),
+            ("second".to_string(), names(&["b"])),
+        ]);
+
+        let groups = group_volumes_by_containers(&volumes, &container_volumes);
+        assert_eq!(groups.len(), 2);
+        assert!(groups.iter().all(|group| group.volumes.len() == 1 && group.containers.len() == 1));
+        assert!(group_volumes_by_containers(&[], &BTreeMap::new()).is_empty());
+    }
+
+    /// An in-memory destination that only records which files were deleted.
+    struct FakeStorage {
+        files: Vec<String>,
+        deleted: RefCell<Vec<String>>,
+    }
+
+    impl FakeStorage {
+        fn new(files: &[String]) -> Self {
+            FakeStorage { files: files.to_vec(), deleted: RefCell::new(Vec::new()) }
+        }
+    }
+
+    impl StorageBackend for FakeStorage {
+        fn upload_file(&self, _file_name: &str, _local_file_path: &str) -> Result<(), Box<dyn Error>> {
+            Err("Not supported.".into())
+        }
+
+        fn download_file(&self, _file_name: &str, _local_file_path: &str) -> Result<(), Box<dyn Error>> {
+            Err("Not supported.".into())
+        }
+
+        fn list_files(&self) -> Result<Vec<String>, Box<dyn Error>> {
+            Ok(self.files.clone())
+        }
+
+        fn delete_file(&self, file_name: &str) -> Result<(), Box<dyn Error>> {
+            self.deleted.borrow_mut().push(file_name.to_string());
+            Ok(())
+        }
+
+        fn stat_file(&self, _file_name: &str) -> Result<FileStat, Box<dyn Error>> {
+            Err("Not supported.".into())
+        }
+
+        fn location(&self, file_name: &str) -> String {
+            file_name.to_string()
+        }
+    }
+
+    /// Returns the name of a backup created the given number of hours ago, named like `run_backup` does.
+    fn backup_name(hours_ago: i64, extension: &str) -> String {
+        let date = Local::now() - Duration::hours(hours_ago);
+        format!("backup-{}.{}", date.format("%Y-%m-%dT%H-%M-%S"), extension)
+    }
+
+    #[test]
+    fn parse_backup_date_reads_the_timestamp_in_local_time() {
+        let now = Utc::now();
+        let date = parse_backup_date(&backup_name(0, "tar.gz")).unwrap();
+        assert!((now - date).num_seconds().abs() <= 1, "{} is not close to {}", date, now);
+
+        let expected = Local.with_ymd_and_hms(2024, 9, 10, 16, 2, 47).earliest().unwrap().with_timezone(&Utc);
+        assert_eq!(parse_backup_date("backup-2024-09-10T16-02-47.tar.zst"), Some(expected));
+        assert_eq!(parse_backup_date("backup-2024-09-10T16-02-47.tar.gz.partial"), None);
+        assert_eq!(parse_backup_date("backup-latest.tar.gz"), None);
+    }
+
+    #[test]
+    fn get_latest_backup_file_name_ignores_partial_uploads() {
+        let newest_complete = backup_name(2, "tar.gz");
+        let storage = FakeStorage::new(&[
+            backup_name(5, "tar.gz"),
+            newest_complete.clone(),
+            partial_file_name(&backup_name(1, "tar.gz")),
+        ]);
+        assert_eq!(storage.get_latest_backup_file_name().unwrap(), newest_complete);
+
+        let storage = FakeStorage::new(&[partial_file_name(&backup_name(1, "tar.gz"))]);
+        assert!(storage.get_latest_backup_file_name().is_err());
+    }
+
+    #[test]
+    fn remove_old_backups_does_not_count_partial_uploads() {
+        let older = backup_name(48, "tar.gz");
+        let newer = backup_name(24, "tar.zst");
+        let in_progress = partial_file_name(&backup_name(1, "tar.gz"));
+        let storage = FakeStorage::new(&[older.clone(), newer, in_progress]);
+        let retention = RetentionPolicy { count: 1, period: 30 };
+
+        remove_old_backups(&storage, &retention).unwrap();
+        assert_eq!(*storage.deleted.borrow(), vec![older]);
+    }
+
+    #[test]
+    fn remove_old_backups_removes_partial_uploads_older_than_a_day() {
+        let stale = partial_file_name(&backup_name(25, "tar.gz"));
+        let recent = partial_file_name(&backup_name(23, "tar.gz"));
+        let storage = FakeStorage::new(&[stale.clone(), recent, "notes.txt.partial".to_string()]);
+        let retention = RetentionPolicy { count: 1, period: 30 };
+
+        remove_old_backups(&storage, &retention).unwrap();
+        assert_eq!(*storage.deleted.borrow(), vec![stale]);
+    }
 }
@@ -5,7 +5,10 @@ mod utility;
 use crate::backup::{configure_cron_scheduled_backup, run_backup};
 use crate::restore::restore_volumes;
 use crate::utility::configs::retention_policy::RetentionPolicy;
-use crate::utility::configs::server_config::ServerConfig;
+use crate::utility::configs::retry_policy::RetryPolicy;
+use crate::utility::configs::backup_config::BackupConfig;
+use crate::utility::configs::destination_config::DestinationConfig;
+use crate::utility::container_guard::resume_containers_on_termination;
 use std::env;
 use std::error::Error;
 
@@ -13,9 +16,13 @@ use std::error::Error;
 async fn main() -> Result<(), Box<dyn Error>> {
     dotenv::dotenv().ok();
 
-    let server_config = ServerConfig::new_from_env(".ssh/id_rsa".to_string())?;
+    let destinations = DestinationConfig::new_all_from_env()?;
+    let retry_policy = RetryPolicy::new_from_env()?;
+    let backup_config = BackupConfig::new_from_env()?;
     let action = env::var("ACTION")?;
 
+    resume_containers_on_termination()?;
+
     const BACKUP_TEMP_PATH: &str = "backup-temp";
 
     match action.as_str() {
@@ -23,16 +30,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let retention_config = RetentionPolicy::new_from_env()?;
 
             if let Ok(backup_cron) = env::var("BACKUP_CRON") {
-                configure_cron_scheduled_backup(&server_config,
+                configure_cron_scheduled_backup(&destinations,
                                                 &retention_config,
+                                                &retry_policy,
+                                                &backup_config,
                                                 &backup_cron,
                                                 BACKUP_TEMP_PATH).await?;
-            } else { run_backup(&server_config, &retention_config, BACKUP_TEMP_PATH)?; }
+            } else { run_backup(&destinations, &retention_config, &retry_policy, &backup_config, BACKUP_TEMP_PATH)?; }
         }
         "restore" => {
             let backup_to_be_restored = env::var("BACKUP_TO_BE_RESTORED")?;
             let volume_to_be_restored = env::var("VOLUME_TO_BE_RESTORED")?;
-            restore_volumes(&server_config,
+            let source = DestinationConfig::select(&destinations, env::var("RESTORE_DESTINATION").ok().as_deref())?;
+            restore_volumes(source,
+                            &retry_policy,
+                            &backup_config,
                             &backup_to_be_restored,
                             &volume_to_be_restored,
                             BACKUP_TEMP_PATH)?;
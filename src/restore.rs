@@ -1,28 +1,33 @@
-use crate::backup::run_backup;
-use crate::utility::compression::decompress_file_from_tar;
+use crate::backup::{get_volume_path, run_backup};
+use crate::utility::compression::{decompress_file_from_tar, strip_archive_extension, Codec};
+use crate::utility::configs::backup_config::BackupConfig;
 use crate::utility::configs::retention_policy::RetentionPolicy;
-use crate::utility::configs::server_config::ServerConfig;
-use crate::utility::docker::{start_containers, stop_containers};
-use crate::utility::server::Server;
+use crate::utility::configs::retry_policy::RetryPolicy;
+use crate::utility::configs::destination_config::DestinationConfig;
+use crate::utility::configs::docker_config::DockerConfig;
+use crate::utility::container_guard::ContainerGuard;
+use crate::utility::docker::get_container_ids;
 use fs_extra::dir::CopyOptions;
 use fs_extra::{move_items, remove_items};
 use std::error::Error;
 use std::fs;
 use std::path::Path;
 
-/// Restores specified Docker volumes from a backup file on a remote server.
+/// Restores specified Docker volumes from a backup file stored in the given source destination.
 ///
 /// This function performs the following steps:
 /// 1. Determines which backup file to restore, either the latest or a specified one.
-/// 2. Downloads the backup file from the remote server.
+/// 2. Downloads the backup file from the destination.
 /// 3. Extracts the specified volumes from the backup file.
-/// 4. Performs a backup before the restoration process.
+/// 4. Performs a backup to the source destination before the restoration process.
 /// 5. Replaces the existing volume data with the extracted data.
 /// 6. Cleans up temporary files and directories.
 ///
 /// # Arguments
 ///
-/// * `server_config` - A reference to a `ServerConfig` containing connection info to the server.
+/// * `source` - The destination the backup is restored from, which also stores the backup made before the restoration.
+/// * `retry_policy` - A reference to a `RetryPolicy` that defines how failed operations on the destination are retried.
+/// * `backup_config` - A reference to a `BackupConfig` that defines how the volumes are backed up before the restoration.
 /// * `backup_to_be_restored` - A string slice representing the backup file to restore, or "latest" for the most recent backup.
 /// * `volumes_to_be_restored` - A string slice representing the volumes to restore, comma-separated, or "all" to restore all volumes.
 /// * `temp_path` - A string slice representing the path to a temporary directory for storing the backup during restoration.
@@ -30,26 +35,27 @@ use std::path::Path;
 /// # Returns
 ///
 /// * `Result<(), Box<dyn Error>>` - An empty result if the restoration is successful, or an error if something goes wrong.
-pub fn restore_volumes(server_config: &ServerConfig,
+pub fn restore_volumes(source: &DestinationConfig,
+                       retry_policy: &RetryPolicy,
+                       backup_config: &BackupConfig,
                        backup_to_be_restored: &str,
                        volumes_to_be_restored: &str,
                        temp_path: &str) -> Result<(), Box<dyn Error>> {
-    let server = Server::new(server_config.clone());
+    let storage = source.storage.create_backend(retry_policy);
 
     // Create the temp directory if it doesn't exist
     if !Path::new(temp_path).exists() { fs::create_dir_all(temp_path)?; }
 
     // Determine the backup file to restore (either specified or the latest)
     let backup_file_name = if backup_to_be_restored == "latest" {
-        server.get_latest_backup_file_name()?
+        storage.get_latest_backup_file_name()?
     } else { backup_to_be_restored.to_string() };
 
-    // Define paths for the local and remote backup files
+    // Define the path for the local backup file
     let local_backup_path = format!("{}/{}", temp_path, backup_file_name);
-    let remote_backup_path = format!("{}/{}", server_config.server_directory, backup_file_name);
 
-    // Download the backup file from the remote server
-    server.download_file(&remote_backup_path, &local_backup_path)?;
+    // Download the backup file from the destination
+    storage.download_file(&backup_file_name, &local_backup_path)?;
 
     // Define the temporary path for extracted volumes
     let volumes_temp_path = format!("{}/volumes", temp_path);
@@ -57,15 +63,16 @@ pub fn restore_volumes(server_config: &ServerConfig,
     // Extract the specified volumes from the backup file
     let volume_names = extract_volumes_from_backup(&local_backup_path, volumes_to_be_restored, &volumes_temp_path)?;
 
-    // Perform a backup before restoration
-    run_backup(server_config, &RetentionPolicy::new_no_delete(), &format!("{}/before-restore", temp_path))?;
+    // Perform a backup before restoration, only to the source as it is known to be reachable
+    run_backup(std::slice::from_ref(source), &RetentionPolicy::new_no_delete(), retry_policy, backup_config, &format!("{}/before-restore", temp_path))?;
 
     // Restore each volume by decompressing and replacing existing data
     for volume in &volume_names {
-        let volume_backup_path = format!("{}/{}.tar.gz", volumes_temp_path, volume);
+        let volume_backup_path = find_volume_archive(&volumes_temp_path, volume)?;
         let volume_extract_path = format!("{}/{}", volumes_temp_path, volume);
         decompress_file_from_tar(&volume_backup_path, &volume_extract_path)?;
-        replace_volume_data_with_dir(&volume_extract_path, volume)?;
+        let volume_path = get_volume_path(backup_config, volume)?;
+        replace_volume_data_with_dir(&backup_config.docker, &volume_extract_path, volume, &volume_path)?;
     }
 
     // Clean up temporary files
@@ -93,7 +100,7 @@ pub fn restore_volumes(server_config: &ServerConfig,
 fn extract_volumes_from_backup(local_backup_path: &str,
                                volumes_to_be_restored: &str,
                                temp_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    // Decompress the entire tar.gz archive to the temporary directory
+    // Decompress the entire archive to the temporary directory
     decompress_file_from_tar(local_backup_path, temp_path)?;
 
     // Return the names of all volumes or the specified ones
@@ -106,8 +113,8 @@ fn extract_volumes_from_backup(local_backup_path: &str,
 
 /// Retrieves the names of all volumes from a directory.
 ///
-/// This function scans a directory and returns the names of all files that have a `.tar.gz`
-/// extension, representing the volumes.
+/// This function scans a directory and returns the names of all files that have the archive
+/// extension of any codec (e.g. `.tar.gz` or `.tar.zst`), representing the volumes.
 ///
 /// # Arguments
 ///
@@ -115,19 +122,35 @@ fn extract_volumes_from_backup(local_backup_path: &str,
 ///
 /// # Returns
 ///
-/// * `Result<Vec<String>, Box<dyn Error>>` - A vector of volume names (without the `.tar.gz` extension), or an error if something goes wrong.
+/// * `Result<Vec<String>, Box<dyn Error>>` - A vector of volume names (without the archive extension), or an error if something goes wrong.
 fn get_names_of_all_volumes(dir_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
     let volumes: Vec<String> = fs::read_dir(dir_path)?
         .filter_map(|entry| entry.ok())
         .filter(|entry| entry.path().is_file())
         .filter_map(|entry| entry.file_name().into_string().ok())
-        .filter(|name| name.ends_with(".tar.gz"))
-        .map(|name| name.trim_end_matches(".tar.gz").to_string())
+        .filter_map(|name| strip_archive_extension(&name).map(|volume| volume.to_string()))
         .collect();
 
     Ok(volumes)
 }
 
+/// Finds the archive of a volume in a directory, regardless of the codec it was compressed with.
+///
+/// # Arguments
+///
+/// * `dir_path` - A string slice representing the path to the directory containing the volumes.
+/// * `volume` - The name of the volume.
+///
+/// # Returns
+///
+/// * `Result<String, Box<dyn Error>>` - The path of the volume's archive, or an error if the backup does not contain the volume.
+fn find_volume_archive(dir_path: &str, volume: &str) -> Result<String, Box<dyn Error>> {
+    Codec::ALL.iter()
+        .map(|codec| format!("{}/{}.{}", dir_path, volume, codec.extension()))
+        .find(|path| Path::new(path).is_file())
+        .ok_or_else(|| format!("The backup does not contain the volume {}.", volume).into())
+}
+
 /// Replaces the data in a Docker volume with the contents of a specified directory.
 ///
 /// This function performs the following steps:
@@ -138,35 +161,38 @@ fn get_names_of_all_volumes(dir_path: &str) -> Result<Vec<String>, Box<dyn Error
 ///
 /// # Arguments
 ///
+/// * `docker_config` - The connection to the Docker daemon managing the containers.
 /// * `dir_path` - A string slice representing the path to the directory containing the new volume data.
 /// * `volume_name` - A string slice representing the name of the Docker volume to be replaced.
+/// * `volume_path` - The path where the data of the volume is accessible.
 ///
 /// # Returns
 ///
 /// * `Result<(), Box<dyn Error>>` - An empty result if the replacement is successful, or an error if something goes wrong.
-pub fn replace_volume_data_with_dir(dir_path: &str, volume_name: &str) -> Result<(), Box<dyn Error>> {
-    // Stop containers using the specified volume
-    let container_ids = stop_containers(volume_name)?;
-
-    // Define the path where the volume is mounted inside the container
-    let container_path = format!("/backup/{}", volume_name);
+pub fn replace_volume_data_with_dir(docker_config: &DockerConfig,
+                                    dir_path: &str,
+                                    volume_name: &str,
+                                    volume_path: &str) -> Result<(), Box<dyn Error>> {
+    // Stop containers using the specified volume, the guard restarts them if anything below fails
+    let mut guard = ContainerGuard::new(docker_config);
+    guard.stop(&get_container_ids(docker_config, volume_name)?)?;
 
     // Check if the volume mount point exists
-    if !Path::new(&container_path).exists() {
+    if !Path::new(volume_path).exists() {
         return Err(format!("Volume {} does not exist.", volume_name).into());
     }
 
     // Remove existing data in the volume's mount point
-    let volume_data = collect_paths(&container_path)?;
+    let volume_data = collect_paths(volume_path)?;
     remove_items(&volume_data)?;
 
     // Move the new data from the extracted directory to the volume's mount point
-    let dir_data = collect_paths(&dir_path)?;
+    let dir_data = collect_paths(dir_path)?;
     let options = CopyOptions::new();
-    move_items(&dir_data, &container_path, &options)?;
+    move_items(&dir_data, volume_path, &options)?;
 
     // Restart the containers that were stopped
-    start_containers(container_ids)?;
+    guard.resume_all()?;
     Ok(())
 }
 
@@ -1,85 +1,488 @@
-use flate2::bufread::GzDecoder;
+use crate::utility::configs::compression_config::CompressionConfig;
+use flate2::bufread::MultiGzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use std::error::Error;
 use std::fs::File;
 use std::io;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::Path;
+use std::thread;
 use tar::{Archive, Builder};
+use xz2::stream::{Check, MtStreamBuilder};
 
-/// Compresses an entire folder into a .tar.gz archive.
+/// The window size (as a power of two) used by zstd's long-distance matching, equal to `zstd --long`.
+const ZSTD_LONG_WINDOW_LOG: u32 = 27;
+
+/// The size of the blocks that are compressed in parallel by `ParallelGzEncoder`.
+const PARALLEL_GZIP_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// The codecs archives can be compressed with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+    Xz,
+    Lz4,
+    None,
+}
+
+impl Codec {
+    /// All codecs, used for recognizing archive extensions.
+    pub const ALL: [Codec; 5] = [Codec::Gzip, Codec::Zstd, Codec::Xz, Codec::Lz4, Codec::None];
+
+    /// Parses the name of a codec as used in the configuration, e.g. `zstd`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the name does not belong to any codec.
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        Self::ALL.into_iter()
+            .find(|codec| codec.name() == name.trim().to_lowercase())
+            .ok_or_else(|| format!("Unknown compression '{}'. Use 'gzip', 'zstd', 'xz', 'lz4' or 'none'.", name).into())
+    }
+
+    /// Returns the name of the codec as used in the configuration.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+            Codec::Lz4 => "lz4",
+            Codec::None => "none",
+        }
+    }
+
+    /// Returns the file extension of archives compressed with the codec, without a leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Codec::Gzip => "tar.gz",
+            Codec::Zstd => "tar.zst",
+            Codec::Xz => "tar.xz",
+            Codec::Lz4 => "tar.lz4",
+            Codec::None => "tar",
+        }
+    }
+
+    /// Returns the lowest and the highest compression level supported by the codec,
+    /// or `None` if the codec has no levels.
+    pub fn level_range(&self) -> Option<(i32, i32)> {
+        match self {
+            Codec::Gzip | Codec::Xz => Some((0, 9)),
+            Codec::Zstd => Some((1, 22)),
+            Codec::Lz4 | Codec::None => None,
+        }
+    }
+
+    /// Detects the codec of compressed data from the magic bytes at its start.
+    ///
+    /// Data that does not start with the magic bytes of any codec is treated as uncompressed.
+    fn detect(header: &[u8]) -> Self {
+        const MAGIC_BYTES: [(Codec, &[u8]); 4] = [
+            (Codec::Gzip, &[0x1f, 0x8b]),
+            (Codec::Zstd, &[0x28, 0xb5, 0x2f, 0xfd]),
+            (Codec::Xz, &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]),
+            (Codec::Lz4, &[0x04, 0x22, 0x4d, 0x18]),
+        ];
+
+        MAGIC_BYTES.into_iter()
+            .find(|(_, magic_bytes)| header.starts_with(magic_bytes))
+            .map(|(codec, _)| codec)
+            .unwrap_or(Codec::None)
+    }
+}
+
+/// Strips the archive extension of any codec from a file name.
+///
+/// # Arguments
+///
+/// * `file_name` - The file name, e.g. `backup-2024-09-10T16-02-47.tar.zst`.
+///
+/// # Returns
+///
+/// * `Option<&str>` - The file name without the extension, or `None` if it is not an archive.
+pub fn strip_archive_extension(file_name: &str) -> Option<&str> {
+    Codec::ALL.iter()
+        .find_map(|codec| file_name.strip_suffix(codec.extension())?.strip_suffix('.'))
+}
+
+/// A writer that compresses everything written to it with the configured codec.
+enum Encoder<W: Write> {
+    Gzip(GzEncoder<W>),
+    ParallelGzip(ParallelGzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Xz(xz2::write::XzEncoder<W>),
+    Lz4(lz4_flex::frame::FrameEncoder<W>),
+    None(W),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates an encoder writing the compressed data to `writer`.
+    fn new(writer: W, config: &CompressionConfig) -> io::Result<Self> {
+        Ok(match config.codec {
+            Codec::Gzip => {
+                let level = config.level.map(|level| Compression::new(level as u32)).unwrap_or_default();
+                if config.threads > 1 {
+                    Encoder::ParallelGzip(ParallelGzEncoder::new(writer, level, config.threads))
+                } else { Encoder::Gzip(GzEncoder::new(writer, level)) }
+            }
+            Codec::Zstd => {
+                let mut encoder = zstd::Encoder::new(writer, config.level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL))?;
+                if config.long_distance_matching {
+                    encoder.long_distance_matching(true)?;
+                    encoder.window_log(ZSTD_LONG_WINDOW_LOG)?;
+                }
+                if config.threads > 1 { encoder.multithread(config.threads as u32)?; }
+                Encoder::Zstd(encoder)
+            }
+            Codec::Xz => {
+                let preset = config.level.unwrap_or(6) as u32;
+                if config.threads > 1 {
+                    let stream = MtStreamBuilder::new().threads(config.threads as u32).preset(preset).check(Check::Crc64).encoder()?;
+                    Encoder::Xz(xz2::write::XzEncoder::new_stream(writer, stream))
+                } else { Encoder::Xz(xz2::write::XzEncoder::new(writer, preset)) }
+            }
+            Codec::Lz4 => Encoder::Lz4(lz4_flex::frame::FrameEncoder::new(writer)),
+            Codec::None => Encoder::None(writer),
+        })
+    }
+
+    /// Writes the end of the compressed stream and returns the underlying writer.
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::ParallelGzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+            Encoder::Xz(encoder) => encoder.finish(),
+            Encoder::Lz4(encoder) => encoder.finish().map_err(io::Error::other),
+            Encoder::None(writer) => Ok(writer),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::ParallelGzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+            Encoder::Xz(encoder) => encoder.write(buf),
+            Encoder::Lz4(encoder) => encoder.write(buf),
+            Encoder::None(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::ParallelGzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+            Encoder::Xz(encoder) => encoder.flush(),
+            Encoder::Lz4(encoder) => encoder.flush(),
+            Encoder::None(writer) => writer.flush(),
+        }
+    }
+}
+
+/// A gzip encoder that compresses blocks of its input on multiple threads, like `pigz`.
+///
+/// Every block is written as a separate gzip member. Concatenated members form a valid gzip
+/// file that any gzip implementation decompresses as a whole. Empty input is written as a single
+/// empty member, as an empty file is not valid gzip.
+struct ParallelGzEncoder<W: Write> {
+    writer: W,
+    level: Compression,
+    threads: usize,
+    blocks: Vec<Vec<u8>>,
+    has_members: bool,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    /// Creates an encoder compressing up to `threads` blocks at a time and writing them to `writer`.
+    fn new(writer: W, level: Compression, threads: usize) -> Self {
+        Self { writer, level, threads, blocks: Vec::with_capacity(threads), has_members: false }
+    }
+
+    /// Compresses all buffered blocks in parallel and writes them to the underlying writer in order.
+    fn compress_blocks(&mut self) -> io::Result<()> {
+        let level = self.level;
+        let members: Vec<io::Result<Vec<u8>>> = thread::scope(|scope| {
+            let handles: Vec<_> = self.blocks.iter()
+                .map(|block| scope.spawn(move || {
+                    let mut encoder = GzEncoder::new(Vec::with_capacity(block.len() / 2), level);
+                    encoder.write_all(block)?;
+                    encoder.finish()
+                }))
+                .collect();
+
+            handles.into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|_| Err(io::Error::other("A compression thread panicked."))))
+                .collect()
+        });
+
+        for member in members {
+            self.writer.write_all(&member?)?;
+            self.has_members = true;
+        }
+        self.blocks.clear();
+
+        Ok(())
+    }
+
+    /// Compresses the remaining input and returns the underlying writer.
+    fn finish(mut self) -> io::Result<W> {
+        if !self.has_members && self.blocks.is_empty() { self.blocks.push(Vec::new()); }
+        self.compress_blocks()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let has_space = self.blocks.last().is_some_and(|block| block.len() < PARALLEL_GZIP_BLOCK_SIZE);
+        if !has_space {
+            if self.blocks.len() == self.threads { self.compress_blocks()?; }
+            self.blocks.push(Vec::with_capacity(PARALLEL_GZIP_BLOCK_SIZE));
+        }
+
+        let block = self.blocks.last_mut().unwrap();
+        let length = buf.len().min(PARALLEL_GZIP_BLOCK_SIZE - block.len());
+        block.extend_from_slice(&buf[..length]);
+
+        Ok(length)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.compress_blocks()?;
+        self.writer.flush()
+    }
+}
+
+/// Compresses an entire folder into a tar archive.
 ///
 /// This function takes a folder path and compresses its contents, including all subdirectories,
-/// into a .tar.gz file at the specified tar_path. The resulting archive includes all files
-/// and directories from the source folder, preserving the directory structure.
+/// into a tar archive at the specified tar_path, compressed with the configured codec. The resulting
+/// archive includes all files and directories from the source folder, preserving the directory structure.
 ///
 /// # Arguments
 ///
 /// * `folder_path` - The path to the folder that should be compressed.
-/// * `tar_path` - The path where the resulting .tar.gz file will be created.
+/// * `tar_path` - The path where the resulting archive will be created.
+/// * `config` - The codec and level the archive is compressed with.
 ///
 /// # Returns
 ///
 /// * `io::Result<()>` - An empty result if successful, or an I/O error if something goes wrong.
-pub fn compress_folder_to_tar(folder_path: &str, tar_path: &str) -> io::Result<()> {
-    let tar_gz = File::create(tar_path)?;
-    let enc = GzEncoder::new(tar_gz, Compression::default());
+pub fn compress_folder_to_tar(folder_path: &str, tar_path: &str, config: &CompressionConfig) -> io::Result<()> {
+    let tar_file = File::create(tar_path)?;
+    let enc = Encoder::new(tar_file, config)?;
     let mut tar = Builder::new(enc);
 
     tar.append_dir_all(".", folder_path)?;
+    tar.into_inner()?.finish()?;
 
     Ok(())
 }
 
-/// Compresses multiple files into a single .tar.gz archive.
+/// Compresses multiple files into a single tar archive.
 ///
-/// This function takes a list of file paths and compresses them into a single .tar.gz
-/// file at the specified combined_path. Each file is added to the archive under its
-/// original file name, without any directory structure.
+/// This function takes a list of file paths and compresses them into a single tar archive
+/// at the specified combined_path, compressed with the configured codec. Each file is added
+/// to the archive under its original file name, without any directory structure.
 ///
 /// # Arguments
 ///
 /// * `files_paths` - An array of strings representing the paths of the files to be compressed.
-/// * `combined_path` - The path where the resulting .tar.gz file will be created.
+/// * `combined_path` - The path where the resulting archive will be created.
+/// * `config` - The codec and level the archive is compressed with.
 ///
 /// # Returns
 ///
 /// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error if something goes wrong.
-pub fn compress_files_to_tar(files_paths: &[String], combined_path: &str) -> Result<(), Box<dyn Error>> {
-    let tar_gz = File::create(combined_path)?;
-    let enc = GzEncoder::new(tar_gz, Compression::default());
+pub fn compress_files_to_tar(files_paths: &[String],
+                             combined_path: &str,
+                             config: &CompressionConfig) -> Result<(), Box<dyn Error>> {
+    let tar_file = File::create(combined_path)?;
+    let enc = Encoder::new(tar_file, config)?;
     let mut tar = Builder::new(enc);
 
     for file_path in files_paths {
         let mut file = File::open(file_path)?;
         tar.append_file(Path::new(file_path).file_name().unwrap(), &mut file)?;
     }
+    tar.into_inner()?.finish()?;
 
     Ok(())
 }
 
-/// Decompresses a .tar.gz archive into a specified output directory.
+/// Decompresses a tar archive into a specified output directory.
 ///
-/// This function takes the path of a .tar.gz file and decompresses its contents into
-/// the specified output directory. The directory structure stored in the archive is
-/// preserved during extraction.
+/// This function takes the path of a tar archive and decompresses its contents into
+/// the specified output directory. The codec is detected from the magic bytes at the start
+/// of the file, so archives of any supported codec can be restored regardless of the
+/// current configuration. The directory structure stored in the archive is preserved during extraction.
 ///
 /// # Arguments
 ///
-/// * `tar_gz_path` - The path to the .tar.gz file that should be decompressed.
+/// * `tar_path` - The path to the archive that should be decompressed.
 /// * `output_dir` - The directory where the archive's contents will be extracted.
 ///
 /// # Returns
 ///
 /// * `io::Result<()>` - An empty result if successful, or an I/O error if something goes wrong.
-pub fn decompress_file_from_tar(tar_gz_path: &str, output_dir: &str) -> io::Result<()> {
-    let tar_gz = File::open(tar_gz_path)?;
-    let tar_gz_reader = BufReader::new(tar_gz);
-    let tar = GzDecoder::new(tar_gz_reader);
-    let mut archive = Archive::new(tar);
+pub fn decompress_file_from_tar(tar_path: &str, output_dir: &str) -> io::Result<()> {
+    let mut tar_reader = BufReader::new(File::open(tar_path)?);
+    let codec = Codec::detect(tar_reader.fill_buf()?);
+
+    let decoder: Box<dyn Read> = match codec {
+        Codec::Gzip => Box::new(MultiGzDecoder::new(tar_reader)),
+        Codec::Zstd => {
+            let mut decoder = zstd::Decoder::with_buffer(tar_reader)?;
+            decoder.window_log_max(ZSTD_LONG_WINDOW_LOG)?;
+            Box::new(decoder)
+        }
+        Codec::Xz => Box::new(xz2::bufread::XzDecoder::new(tar_reader)),
+        Codec::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(tar_reader)),
+        Codec::None => Box::new(tar_reader),
+    };
+    let mut archive = Archive::new(decoder);
 
     archive.unpack(output_dir)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compresses some data with the encoder of a codec.
+    fn compress(codec: Codec, data: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        match codec {
+            Codec::Gzip => {
+                let mut encoder = GzEncoder::new(&mut encoded, Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap();
+            }
+            Codec::Zstd => encoded = zstd::encode_all(data, 3).unwrap(),
+            Codec::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(&mut encoded, 6);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap();
+            }
+            Codec::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(&mut encoded);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap();
+            }
+            Codec::None => encoded.extend_from_slice(data),
+        }
+        encoded
+    }
+
+    #[test]
+    fn detect_recognizes_the_output_of_every_encoder() {
+        for codec in Codec::ALL {
+            assert_eq!(Codec::detect(&compress(codec, b"volume data")), codec, "{}", codec.name());
+        }
+    }
+
+    #[test]
+    fn detect_treats_short_or_unknown_data_as_uncompressed() {
+        assert_eq!(Codec::detect(b""), Codec::None);
+        assert_eq!(Codec::detect(&[0x1f]), Codec::None);
+        assert_eq!(Codec::detect(&[0x28, 0xb5, 0x2f]), Codec::None);
+        assert_eq!(Codec::detect(b"ustar"), Codec::None);
+    }
+
+    #[test]
+    fn strip_archive_extension_removes_the_extension_of_any_codec() {
+        for codec in Codec::ALL {
+            let file_name = format!("backup-2024-09-10T16-02-47.{}", codec.extension());
+            assert_eq!(strip_archive_extension(&file_name), Some("backup-2024-09-10T16-02-47"));
+        }
+        assert_eq!(strip_archive_extension("redis-data.tar.gz"), Some("redis-data"));
+    }
+
+    #[test]
+    fn strip_archive_extension_rejects_other_files() {
+        assert_eq!(strip_archive_extension("backup.zip"), None);
+        assert_eq!(strip_archive_extension("backup.tar.gz.partial"), None);
+        assert_eq!(strip_archive_extension("backuptar"), None);
+        assert_eq!(strip_archive_extension("notes.txt"), None);
+    }
+
+    /// Returns `length` bytes of barely compressible data.
+    fn payload(length: usize) -> Vec<u8> {
+        let mut state: u32 = 0x2545_f491;
+        (0..length).map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8 % 64
+        }).collect()
+    }
+
+    /// Returns a path in the temporary directory that is unique to a test.
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("backup-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn parallel_gzip_round_trips_through_multi_member_decoding() {
+        const THREADS: usize = 3;
+        let lengths = [0, 1000, PARALLEL_GZIP_BLOCK_SIZE, 2 * PARALLEL_GZIP_BLOCK_SIZE,
+                       THREADS * PARALLEL_GZIP_BLOCK_SIZE + 12_345, 2 * THREADS * PARALLEL_GZIP_BLOCK_SIZE + 1];
+
+        for length in lengths {
+            let data = payload(length);
+            let mut encoder = ParallelGzEncoder::new(Vec::new(), Compression::fast(), THREADS);
+            // Odd-sized writes, so blocks are filled across several writes
+            for chunk in data.chunks(77_777) {
+                encoder.write_all(chunk).unwrap();
+            }
+            let encoded = encoder.finish().unwrap();
+
+            let mut decoded = Vec::new();
+            MultiGzDecoder::new(encoded.as_slice()).read_to_end(&mut decoded).unwrap();
+            assert!(decoded == data, "round trip of {} bytes", length);
+        }
+    }
+
+    #[test]
+    fn folder_archives_round_trip_with_every_codec() {
+        let source = temp_path("source");
+        std::fs::create_dir_all(format!("{}/nested", source)).unwrap();
+        std::fs::write(format!("{}/data.bin", source), payload(3 * PARALLEL_GZIP_BLOCK_SIZE + 5)).unwrap();
+        std::fs::write(format!("{}/nested/empty", source), b"").unwrap();
+
+        let configs = [
+            (Codec::Gzip, false, 1),
+            (Codec::Gzip, false, 4),
+            (Codec::Zstd, false, 1),
+            (Codec::Zstd, true, 4),
+            (Codec::Xz, false, 1),
+            (Codec::Xz, false, 4),
+            (Codec::Lz4, false, 1),
+            (Codec::None, false, 1),
+        ];
+        for (codec, long_distance_matching, threads) in configs {
+            let config = CompressionConfig { codec, level: Some(1).filter(|_| codec.level_range().is_some()),
+                                             long_distance_matching, threads };
+            let name = format!("{}-{}-{}", codec.name(), long_distance_matching, threads);
+            let archive = format!("{}.{}", temp_path(&name), codec.extension());
+            let output = temp_path(&format!("{}-output", name));
+
+            compress_folder_to_tar(&source, &archive, &config).unwrap();
+            decompress_file_from_tar(&archive, &output).unwrap();
+
+            assert_eq!(std::fs::read(format!("{}/data.bin", output)).unwrap(),
+                       std::fs::read(format!("{}/data.bin", source)).unwrap(), "{}", name);
+            assert!(std::fs::read(format!("{}/nested/empty", output)).unwrap().is_empty(), "{}", name);
+            std::fs::remove_file(&archive).unwrap();
+            std::fs::remove_dir_all(&output).unwrap();
+        }
+        std::fs::remove_dir_all(&source).unwrap();
+    }
+}
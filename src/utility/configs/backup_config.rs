@@ -0,0 +1,95 @@
+use crate::utility::configs::compression_config::CompressionConfig;
+use crate::utility::configs::docker_config::DockerConfig;
+use crate::utility::configs::hook_config::HookConfig;
+use crate::utility::configs::volume_discovery_config::VolumeDiscoveryConfig;
+use crate::utility::docker::ConsistencyMode;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+
+/// A struct to hold the configuration of how volumes are backed up.
+///
+/// - `concurrency`: How many volumes (or groups of volumes sharing containers) are backed up at the same time.
+/// - `compression`: How the archives are compressed.
+/// - `stop_containers_once`: Whether a container using several volumes is stopped only once for all of them,
+///   instead of once for each volume.
+/// - `consistency_mode`: How the containers using a volume are suspended while it is backed up.
+/// - `volume_consistency_modes`: The consistency modes of volumes that override `consistency_mode`.
+/// - `docker`: The connection to the Docker daemon managing the containers.
+/// - `volume_discovery`: How the volumes to be backed up are found.
+/// - `hooks`: The commands run inside the containers before stopping and after restarting them.
+#[derive(Clone)]
+pub struct BackupConfig {
+    pub concurrency: usize,
+    pub compression: CompressionConfig,
+    pub stop_containers_once: bool,
+    pub consistency_mode: ConsistencyMode,
+    pub volume_consistency_modes: HashMap<String, ConsistencyMode>,
+    pub docker: DockerConfig,
+    pub volume_discovery: VolumeDiscoveryConfig,
+    pub hooks: HookConfig,
+}
+
+impl BackupConfig {
+    /// Creates a new `BackupConfig` instance by loading values from environment variables.
+    ///
+    /// This method reads the following environment variables:
+    ///
+    /// - `BACKUP_CONCURRENCY`: Optional. How many volumes are backed up at the same time. Defaults to `1`.
+    /// - `STOP_CONTAINERS_ONCE`: Optional. Set to `true` to stop and restart a container using several volumes only
+    ///   once for all of them, instead of separately for each volume. Defaults to `false`.
+    /// - `CONSISTENCY_MODE`: Optional. How the containers using a volume are suspended, one of `stop`, `pause`
+    ///   and `none`. Defaults to `stop`.
+    /// - `VOLUME_CONSISTENCY_MODES`: Optional. A comma-separated list of volumes with their own consistency mode,
+    ///   e.g. `my_db:stop,my_media:none`.
+    ///
+    /// The compression is read by `CompressionConfig::new_from_env`, the connection to the Docker daemon
+    /// by `DockerConfig::new_from_env`, the volume discovery by `VolumeDiscoveryConfig::new_from_env`
+    /// and the hooks by `HookConfig::new_from_env`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if `BACKUP_CONCURRENCY` is not a positive number, a consistency mode is unknown,
+    /// or the compression, Docker, volume discovery or hook configuration is invalid.
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let concurrency = match env::var("BACKUP_CONCURRENCY") {
+            Ok(concurrency) => concurrency.parse::<usize>().ok().filter(|concurrency| *concurrency > 0)
+                .ok_or_else(|| format!("Invalid BACKUP_CONCURRENCY '{}', expected a positive number.", concurrency))?,
+            Err(_) => 1,
+        };
+
+        let stop_containers_once = env::var("STOP_CONTAINERS_ONCE").map(|val| val == "true").unwrap_or(false);
+
+        let consistency_mode = match env::var("CONSISTENCY_MODE") {
+            Ok(mode) => ConsistencyMode::from_name(&mode)?,
+            Err(_) => ConsistencyMode::Stop,
+        };
+        let volume_consistency_modes = match env::var("VOLUME_CONSISTENCY_MODES") {
+            Ok(modes) => modes.split(',')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(|entry| {
+                    let (volume, mode) = entry.split_once(':')
+                        .ok_or_else(|| format!("Invalid VOLUME_CONSISTENCY_MODES entry '{}', expected volume:mode.", entry))?;
+                    Ok((volume.trim().to_string(), ConsistencyMode::from_name(mode)?))
+                })
+                .collect::<Result<HashMap<String, ConsistencyMode>, Box<dyn Error>>>()?,
+            Err(_) => HashMap::new(),
+        };
+
+        Ok(Self {
+            concurrency,
+            compression: CompressionConfig::new_from_env()?,
+            stop_containers_once,
+            consistency_mode,
+            volume_consistency_modes,
+            docker: DockerConfig::new_from_env()?,
+            volume_discovery: VolumeDiscoveryConfig::new_from_env()?,
+            hooks: HookConfig::new_from_env()?,
+        })
+    }
+
+    /// Returns the consistency mode of a volume, which is its own mode if configured, or the global one.
+    pub fn consistency_mode_for(&self, volume: &str) -> ConsistencyMode {
+        self.volume_consistency_modes.get(volume).copied().unwrap_or(self.consistency_mode)
+    }
+}
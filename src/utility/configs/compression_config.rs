@@ -0,0 +1,67 @@
+use crate::utility::compression::Codec;
+use std::env;
+use std::error::Error;
+use std::thread;
+
+/// A struct to hold the configuration of how backups are compressed.
+///
+/// The `CompressionConfig` struct contains the following fields:
+///
+/// - `codec`: The codec the archives are compressed with.
+/// - `level`: The compression level, or `None` for the default level of the codec.
+/// - `long_distance_matching`: Whether zstd searches for matches across a large window (like `zstd --long`),
+///   which improves the ratio for large volumes with repeated data at the cost of memory.
+/// - `threads`: The number of threads compressing in parallel. gzip, zstd and xz use multiple threads.
+#[derive(Clone)]
+pub struct CompressionConfig {
+    pub codec: Codec,
+    pub level: Option<i32>,
+    pub long_distance_matching: bool,
+    pub threads: usize,
+}
+
+impl CompressionConfig {
+    /// Creates a new `CompressionConfig` instance by loading values from environment variables.
+    ///
+    /// This method reads the following environment variables:
+    ///
+    /// - `COMPRESSION`: Optional. The codec, one of `gzip`, `zstd`, `xz`, `lz4` and `none`. Defaults to `gzip`.
+    /// - `COMPRESSION_LEVEL`: Optional. The compression level, `0`-`9` for gzip and xz, `1`-`22` for zstd.
+    ///   Defaults to the default level of the codec. Ignored for lz4 and none.
+    /// - `COMPRESSION_ZSTD_LONG`: Optional. Set to `true` to enable long-distance matching for zstd.
+    /// - `COMPRESSION_THREADS`: Optional. The number of threads compressing in parallel, or `0` for one per CPU core.
+    ///   Defaults to `1`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the codec is unknown, the level is not a number or out of range for the codec,
+    /// or the number of threads is not a number.
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let codec = match env::var("COMPRESSION") {
+            Ok(name) => Codec::from_name(&name)?,
+            Err(_) => Codec::Gzip,
+        };
+        let level = match env::var("COMPRESSION_LEVEL") {
+            Ok(level) => Some(level.parse::<i32>()
+                .map_err(|_| format!("Invalid COMPRESSION_LEVEL '{}', expected a number.", level))?),
+            Err(_) => None,
+        };
+        let long_distance_matching = env::var("COMPRESSION_ZSTD_LONG").map(|val| val == "true").unwrap_or(false);
+        let threads = match env::var("COMPRESSION_THREADS") {
+            Ok(threads) => threads.parse::<usize>()
+                .map_err(|_| format!("Invalid COMPRESSION_THREADS '{}', expected a number.", threads))?,
+            Err(_) => 1,
+        };
+        let threads = if threads == 0 {
+            thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1)
+        } else { threads };
+
+        if let (Some(level), Some((min, max))) = (level, codec.level_range()) {
+            if level < min || level > max {
+                return Err(format!("Invalid COMPRESSION_LEVEL {} for {}, expected {} to {}.", level, codec.name(), min, max).into());
+            }
+        }
+
+        Ok(Self { codec, level, long_distance_matching, threads })
+    }
+}
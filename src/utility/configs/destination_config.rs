@@ -0,0 +1,63 @@
+use crate::utility::configs::storage_config::StorageConfig;
+use std::env;
+use std::error::Error;
+
+/// A named destination where backups are stored.
+///
+/// - `name`: The name of the destination, used in log messages and for selecting the restore source.
+/// - `storage`: The configuration of the storage backend of the destination.
+#[derive(Clone)]
+pub struct DestinationConfig {
+    pub name: String,
+    pub storage: StorageConfig,
+}
+
+impl DestinationConfig {
+    /// Creates all configured destinations by loading values from environment variables.
+    ///
+    /// The `DESTINATIONS` environment variable holds a comma-separated list of destination names,
+    /// e.g. `nas,offsite`. The configuration of each destination is read from environment variables
+    /// prefixed with its upper-cased name, e.g. `NAS_STORAGE_TYPE` or `OFFSITE_S3_BUCKET`.
+    ///
+    /// If `DESTINATIONS` is not set, a single destination named `default` is read from the
+    /// environment variables without a prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the configuration of any destination cannot be read.
+    pub fn new_all_from_env() -> Result<Vec<Self>, Box<dyn Error>> {
+        let names: Vec<String> = match env::var("DESTINATIONS") {
+            Ok(names) => names.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect(),
+            Err(_) => return Ok(vec![Self { name: "default".to_string(), storage: StorageConfig::new_from_env("")? }]),
+        };
+
+        if names.is_empty() { return Err("DESTINATIONS does not contain any destination name.".into()); }
+
+        names.into_iter()
+            .map(|name| {
+                let env_prefix = format!("{}_", name.to_uppercase().replace('-', "_"));
+                let storage = StorageConfig::new_from_env(&env_prefix)
+                    .map_err(|e| format!("Invalid configuration of destination '{}': {}", name, e))?;
+                Ok(Self { name, storage })
+            })
+            .collect()
+    }
+
+    /// Selects the destination with the given name from a list of destinations.
+    ///
+    /// # Arguments
+    ///
+    /// * `destinations` - The configured destinations.
+    /// * `name` - The name of the destination, or `None` for the first destination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if no destination with the given name exists.
+    pub fn select<'a>(destinations: &'a [Self], name: Option<&str>) -> Result<&'a Self, Box<dyn Error>> {
+        match name {
+            Some(name) => destinations.iter().find(|destination| destination.name == name)
+                .ok_or_else(|| format!("Unknown destination '{}'.", name).into()),
+            None => destinations.first().ok_or_else(|| "No destination is configured.".into()),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+
+/// A struct to hold the configuration of the connection to the Docker Engine API and of how containers are restarted.
+///
+/// - `socket_path`: The path of the Unix socket the Docker daemon listens on.
+/// - `timeout`: The timeout in seconds for a single request, including stopping a container.
+/// - `dependency_wait`: The time in seconds to wait after starting containers before the containers
+///   depending on them are started.
+/// - `health_timeout`: The time in seconds to wait for restarted containers to become healthy,
+///   or `None` to not wait for them.
+/// - `health_min_running`: The time in seconds a restarted container without a healthcheck must be
+///   running to be considered healthy.
+#[derive(Clone)]
+pub struct DockerConfig {
+    pub socket_path: String,
+    pub timeout: u64,
+    pub dependency_wait: u64,
+    pub health_timeout: Option<u64>,
+    pub health_min_running: u64,
+}
+
+impl DockerConfig {
+    /// Creates a new `DockerConfig` instance by loading values from environment variables.
+    ///
+    /// This method reads the following environment variables:
+    ///
+    /// - `DOCKER_HOST`: Optional. The address of the Docker daemon, which must be a Unix socket like
+    ///   `unix:///var/run/docker.sock`. Defaults to `/var/run/docker.sock`.
+    /// - `DOCKER_TIMEOUT_IN_SECONDS`: Optional. The timeout for a single request. It must be longer than the
+    ///   time the containers need to stop and greater than `0`. Defaults to `120`.
+    /// - `DEPENDENCY_WAIT_IN_SECONDS`: Optional. The time to wait after starting containers before the containers
+    ///   depending on them are started. Defaults to `0`.
+    /// - `HEALTH_TIMEOUT_IN_SECONDS`: Optional. If set, waits up to this long for every restarted container to
+    ///   become healthy, and fails the run if it does not.
+    /// - `HEALTH_MIN_RUNNING_IN_SECONDS`: Optional. How long a restarted container without a healthcheck must be
+    ///   running to be considered healthy. Defaults to `10`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if `DOCKER_HOST` is not a Unix socket, any of the times is not a number or the timeout is `0`.
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let socket_path = match env::var("DOCKER_HOST") {
+            Ok(host) => host.strip_prefix("unix://")
+                .ok_or_else(|| format!("Unsupported DOCKER_HOST '{}', only unix:// sockets are supported.", host))?
+                .to_string(),
+            Err(_) => "/var/run/docker.sock".to_string(),
+        };
+        let timeout = match env::var("DOCKER_TIMEOUT_IN_SECONDS") {
+            Ok(timeout) => timeout.parse::<u64>()
+                .map_err(|_| format!("Invalid DOCKER_TIMEOUT_IN_SECONDS '{}', expected a number.", timeout))?,
+            Err(_) => 120,
+        };
+        // Sockets reject a zero timeout, so every request would fail
+        if timeout == 0 {
+            return Err("Invalid DOCKER_TIMEOUT_IN_SECONDS '0', expected a number greater than 0.".into());
+        }
+        let seconds_from_env = |name: &str| -> Result<Option<u64>, Box<dyn Error>> {
+            match env::var(name) {
+                Ok(seconds) => Ok(Some(seconds.parse::<u64>()
+                    .map_err(|_| format!("Invalid {} '{}', expected a number.", name, seconds))?)),
+                Err(_) => Ok(None),
+            }
+        };
+        let dependency_wait = seconds_from_env("DEPENDENCY_WAIT_IN_SECONDS")?.unwrap_or(0);
+        let health_timeout = seconds_from_env("HEALTH_TIMEOUT_IN_SECONDS")?;
+        let health_min_running = seconds_from_env("HEALTH_MIN_RUNNING_IN_SECONDS")?.unwrap_or(10);
+
+        Ok(Self { socket_path, timeout, dependency_wait, health_timeout, health_min_running })
+    }
+
+    /// Returns the timeout for a single request.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    /// Returns the time to wait between starting containers and the containers depending on them.
+    pub fn dependency_wait(&self) -> Duration {
+        Duration::from_secs(self.dependency_wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_from_env_rejects_a_zero_or_invalid_timeout() {
+        env::set_var("DOCKER_TIMEOUT_IN_SECONDS", "0");
+        assert!(DockerConfig::new_from_env().is_err());
+        env::set_var("DOCKER_TIMEOUT_IN_SECONDS", "-1");
+        assert!(DockerConfig::new_from_env().is_err());
+        env::set_var("DOCKER_TIMEOUT_IN_SECONDS", "1");
+        assert_eq!(DockerConfig::new_from_env().unwrap().timeout(), Duration::from_secs(1));
+        env::remove_var("DOCKER_TIMEOUT_IN_SECONDS");
+    }
+}
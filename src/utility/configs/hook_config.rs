@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+
+/// When a hook runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookStage {
+    /// Before the containers using a volume are stopped.
+    Pre,
+    /// After the containers using a volume are restarted.
+    Post,
+}
+
+impl HookStage {
+    /// Returns the name of the stage as used in log messages, e.g. `pre`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookStage::Pre => "pre",
+            HookStage::Post => "post",
+        }
+    }
+
+    /// Returns the container label holding the hook of the stage, e.g. `backup.exec-pre`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            HookStage::Pre => "backup.exec-pre",
+            HookStage::Post => "backup.exec-post",
+        }
+    }
+}
+
+/// What happens when a hook fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookFailurePolicy {
+    /// The backup of the volume fails.
+    Abort,
+    /// The failure is logged and the backup continues.
+    Warn,
+}
+
+/// A struct to hold the configuration of the commands run inside the containers before and after a backup.
+///
+/// - `pre_hook`: The command run in every container using a backed-up volume before it is stopped.
+/// - `post_hook`: The command run in every container using a backed-up volume after it is restarted.
+/// - `volume_pre_hooks`: The commands run before stopping the containers using a volume, by the environment
+///   variable suffix of the volume (see `env_suffix`).
+/// - `volume_post_hooks`: The commands run after restarting the containers using a volume, by the environment
+///   variable suffix of the volume.
+/// - `timeout`: The time in seconds a hook may run.
+/// - `failure_policy`: What happens when a hook fails.
+#[derive(Clone)]
+pub struct HookConfig {
+    pub pre_hook: Option<String>,
+    pub post_hook: Option<String>,
+    pub volume_pre_hooks: HashMap<String, String>,
+    pub volume_post_hooks: HashMap<String, String>,
+    pub timeout: u64,
+    pub failure_policy: HookFailurePolicy,
+}
+
+impl HookConfig {
+    /// Creates a new `HookConfig` instance by loading values from environment variables.
+    ///
+    /// This method reads the following environment variables:
+    ///
+    /// - `PRE_HOOK`: Optional. The command run in every container using a backed-up volume before it is stopped.
+    /// - `POST_HOOK`: Optional. The command run in every container using a backed-up volume after it is restarted.
+    /// - `PRE_HOOK_<VOLUME>` and `POST_HOOK_<VOLUME>`: Optional. The commands run in the containers using a single
+    ///   volume, where `<VOLUME>` is the upper-cased volume name with all other characters than letters and digits
+    ///   replaced by `_`, e.g. `PRE_HOOK_REDIS_DATA` for `redis-data`.
+    /// - `HOOK_TIMEOUT_IN_SECONDS`: Optional. The time a hook may run. Defaults to `60`.
+    /// - `HOOK_FAILURE_POLICY`: Optional. `abort` to fail the backup of the volume when a hook fails, or `warn` to
+    ///   log the failure and continue. Defaults to `abort`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the timeout is not a number or the failure policy is unknown.
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let non_empty = |command: String| Some(command).filter(|command| !command.trim().is_empty());
+        let volume_hooks = |prefix: &str| -> HashMap<String, String> {
+            env::vars()
+                .filter_map(|(name, command)| Some((name.strip_prefix(prefix)?.to_string(), non_empty(command)?)))
+                .collect()
+        };
+
+        let timeout = match env::var("HOOK_TIMEOUT_IN_SECONDS") {
+            Ok(timeout) => timeout.parse::<u64>()
+                .map_err(|_| format!("Invalid HOOK_TIMEOUT_IN_SECONDS '{}', expected a number.", timeout))?,
+            Err(_) => 60,
+        };
+        let failure_policy = match env::var("HOOK_FAILURE_POLICY").map(|policy| policy.trim().to_lowercase()) {
+            Ok(policy) if policy == "abort" => HookFailurePolicy::Abort,
+            Ok(policy) if policy == "warn" => HookFailurePolicy::Warn,
+            Ok(policy) => return Err(format!("Unknown HOOK_FAILURE_POLICY '{}'. Use 'abort' or 'warn'.", policy).into()),
+            Err(_) => HookFailurePolicy::Abort,
+        };
+
+        Ok(Self {
+            pre_hook: env::var("PRE_HOOK").ok().and_then(non_empty),
+            post_hook: env::var("POST_HOOK").ok().and_then(non_empty),
+            volume_pre_hooks: volume_hooks("PRE_HOOK_"),
+            volume_post_hooks: volume_hooks("POST_HOOK_"),
+            timeout,
+            failure_policy,
+        })
+    }
+
+    /// Returns the configured commands of a stage for a container using the given volumes: the global hook
+    /// followed by the hooks of the volumes, without duplicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `stage` - Whether the hooks run before stopping or after restarting the container.
+    /// * `volumes` - The backed-up volumes the container uses.
+    pub fn commands_for(&self, stage: HookStage, volumes: &[String]) -> Vec<String> {
+        let (global_hook, volume_hooks) = match stage {
+            HookStage::Pre => (&self.pre_hook, &self.volume_pre_hooks),
+            HookStage::Post => (&self.post_hook, &self.volume_post_hooks),
+        };
+
+        let mut commands: Vec<String> = global_hook.iter().cloned().collect();
+        for volume in volumes {
+            if let Some(command) = volume_hooks.get(&env_suffix(volume)) {
+                if !commands.contains(command) { commands.push(command.clone()); }
+            }
+        }
+        commands
+    }
+
+    /// Returns the time a hook may run.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+}
+
+/// Turns a volume name into the suffix of its environment variables, e.g. `REDIS_DATA` for `redis-data`.
+fn env_suffix(volume: &str) -> String {
+    volume.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
@@ -0,0 +1,150 @@
+use crate::utility::configs::server_config::ServerConfig;
+use std::env;
+use std::error::Error;
+
+/// A struct to hold the configuration of a jump host (bastion) the server is reached through.
+///
+/// The `JumpHostConfig` struct contains the following fields:
+///
+/// - `host`: The host name or IP address of the jump host.
+/// - `port`: The port on which the SSH server of the jump host is running.
+/// - `user`: The username for accessing the jump host.
+/// - `ssh_key_path`: The path to the SSH private key used for authenticating to the jump host.
+/// - `ssh_key`: The SSH private key itself, used instead of `ssh_key_path` if set.
+/// - `ssh_key_passphrase`: The passphrase of an encrypted SSH private key.
+/// - `password`: The password used for authenticating to the jump host.
+/// - `host_key_fingerprint`: An optional pinned SHA256 fingerprint of the jump host's host key.
+///   If not set, the host key is verified against the `known_hosts` file.
+#[derive(Clone)]
+pub struct JumpHostConfig {
+    pub host: String,
+    pub port: String,
+    pub user: String,
+    pub ssh_key_path: Option<String>,
+    pub ssh_key: Option<String>,
+    pub ssh_key_passphrase: Option<String>,
+    pub password: Option<String>,
+    pub host_key_fingerprint: Option<String>,
+}
+
+impl JumpHostConfig {
+    /// Creates all configured jump hosts by loading values from environment variables.
+    ///
+    /// The `SSH_JUMP_HOSTS` environment variable holds a comma-separated list of jump hosts in the
+    /// order they are passed through, each written as `[user@]host[:port]`, e.g. `admin@bastion.example.com,10.0.0.5:2222`.
+    /// IPv6 addresses must be enclosed in brackets, e.g. `[2001:db8::1]:2222`. The user defaults to `default_user` and
+    /// the port to `22`.
+    ///
+    /// The credentials and the host key of the n-th jump host (counting from 1) are read from:
+    ///
+    /// - `SSH_JUMP_HOST_<n>_KEY_PATH`: Optional. The path to the SSH private key.
+    /// - `SSH_JUMP_HOST_<n>_KEY`: Optional. The SSH private key itself, with line breaks optionally escaped as `\n`.
+    /// - `SSH_JUMP_HOST_<n>_KEY_PASSPHRASE` or `SSH_JUMP_HOST_<n>_KEY_PASSPHRASE_FILE`: Optional. The passphrase of an
+    ///   encrypted SSH private key.
+    /// - `SSH_JUMP_HOST_<n>_PASSWORD` or `SSH_JUMP_HOST_<n>_PASSWORD_FILE`: Optional. The password for accessing the jump host.
+    /// - `SSH_JUMP_HOST_<n>_HOST_KEY_FINGERPRINT`: Optional. The pinned SHA256 fingerprint of the jump host's host key.
+    ///
+    /// # Arguments
+    ///
+    /// * `env_prefix` - The prefix of all environment variables, e.g. `NAS_` for a destination named `nas`,
+    ///   or an empty string for a single unnamed destination.
+    /// * `default_user` - The username used for jump hosts that do not specify one.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<Self>, Box<dyn Error>>` - The jump hosts in the order they are passed through, or an empty
+    ///   vector if `SSH_JUMP_HOSTS` is not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if a jump host is malformed or a secret file cannot be read.
+    pub fn new_all_from_env(env_prefix: &str, default_user: &str) -> Result<Vec<Self>, Box<dyn Error>> {
+        let jump_hosts = match env::var(format!("{}SSH_JUMP_HOSTS", env_prefix)) {
+            Ok(jump_hosts) => jump_hosts,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        jump_hosts.split(',')
+            .map(|jump_host| jump_host.trim())
+            .filter(|jump_host| !jump_host.is_empty())
+            .enumerate()
+            .map(|(index, jump_host)| {
+                let var_prefix = format!("{}SSH_JUMP_HOST_{}_", env_prefix, index + 1);
+                let var = |name: &str| env::var(format!("{}{}", var_prefix, name)).ok();
+
+                let (user, address) = match jump_host.split_once('@') {
+                    Some((user, address)) => (user.to_string(), address),
+                    None => (default_user.to_string(), jump_host),
+                };
+                let (host, port) = split_host_and_port(address).ok_or_else(|| format!(
+                    "Invalid jump host '{}', expected [user@]host[:port] with IPv6 addresses in brackets.", jump_host))?;
+
+                Ok(Self {
+                    host,
+                    port,
+                    user,
+                    ssh_key_path: var("KEY_PATH"),
+                    ssh_key: ServerConfig::ssh_key_from_env(&format!("{}KEY", var_prefix)),
+                    ssh_key_passphrase: ServerConfig::secret_from_env(&format!("{}KEY_PASSPHRASE", var_prefix))?,
+                    password: ServerConfig::secret_from_env(&format!("{}PASSWORD", var_prefix))?,
+                    host_key_fingerprint: var("HOST_KEY_FINGERPRINT"),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Splits an address written as `host[:port]` or `[ipv6-address][:port]` into the host and the port,
+/// which defaults to `22`.
+///
+/// Returns `None` if the address is malformed, including unbracketed IPv6 addresses, whose port cannot be told apart.
+fn split_host_and_port(address: &str) -> Option<(String, String)> {
+    let (host, port) = match address.strip_prefix('[') {
+        Some(bracketed) => {
+            let (host, rest) = bracketed.split_once(']')?;
+            match rest {
+                "" => (host, None),
+                _ => (host, Some(rest.strip_prefix(':')?)),
+            }
+        }
+        None if address.matches(':').count() > 1 => return None,
+        None => match address.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        },
+    };
+
+    let port = port.unwrap_or("22");
+    if host.is_empty() || port.parse::<u16>().is_err() { return None; }
+    Some((host.to_string(), port.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host_and_port(host: &str, port: &str) -> Option<(String, String)> {
+        Some((host.to_string(), port.to_string()))
+    }
+
+    #[test]
+    fn split_host_and_port_reads_host_names_and_ipv4_addresses() {
+        assert_eq!(split_host_and_port("bastion.example.com"), host_and_port("bastion.example.com", "22"));
+        assert_eq!(split_host_and_port("10.0.0.5:2222"), host_and_port("10.0.0.5", "2222"));
+    }
+
+    #[test]
+    fn split_host_and_port_reads_bracketed_ipv6_addresses() {
+        assert_eq!(split_host_and_port("[2001:db8::1]"), host_and_port("2001:db8::1", "22"));
+        assert_eq!(split_host_and_port("[2001:db8::1]:2222"), host_and_port("2001:db8::1", "2222"));
+        assert_eq!(split_host_and_port("[::1]:22"), host_and_port("::1", "22"));
+    }
+
+    #[test]
+    fn split_host_and_port_rejects_malformed_addresses() {
+        for address in ["2001:db8::1", "::1", "[2001:db8::1", "[2001:db8::1]2222", "[]:22", ":22", "bastion:",
+                        "bastion:ssh", "bastion:70000"] {
+            assert_eq!(split_host_and_port(address), None, "{}", address);
+        }
+    }
+}
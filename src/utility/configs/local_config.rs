@@ -0,0 +1,35 @@
+use std::env;
+use std::error::Error;
+
+/// A struct to hold the configuration of a local backup destination.
+///
+/// The `LocalConfig` struct contains the following fields:
+///
+/// - `directory`: The local directory (e.g. a mounted NFS or CIFS share) where backups are stored.
+#[derive(Clone)]
+pub struct LocalConfig {
+    pub directory: String,
+}
+
+impl LocalConfig {
+    /// Creates a new `LocalConfig` instance by loading values from environment variables.
+    ///
+    /// This method reads the following environment variables:
+    ///
+    /// - `LOCAL_DIRECTORY`: The local directory where backups are stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `env_prefix` - The prefix of all environment variables, e.g. `NAS_` for a destination named `nas`,
+    ///   or an empty string for a single unnamed destination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the environment variables are not set or cannot be read.
+    pub fn new_from_env(env_prefix: &str) -> Result<Self, Box<dyn Error>> {
+        let var = |name: &str| env::var(format!("{}{}", env_prefix, name));
+        let directory = var("LOCAL_DIRECTORY")?;
+
+        Ok(Self { directory })
+    }
+}
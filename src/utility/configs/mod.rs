@@ -1,2 +1,13 @@
 pub mod server_config;
-pub mod retention_policy;
\ No newline at end of file
+pub mod jump_host_config;
+pub mod retention_policy;
+pub mod storage_config;
+pub mod local_config;
+pub mod s3_config;
+pub mod retry_policy;
+pub mod destination_config;
+pub mod compression_config;
+pub mod backup_config;
+pub mod docker_config;
+pub mod volume_discovery_config;
+pub mod hook_config;
@@ -0,0 +1,88 @@
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+
+/// A struct to hold the retry configuration for operations on a backup destination.
+///
+/// - `count`: How many times a failed operation is retried.
+/// - `backoff`: The delay in seconds before the first retry. It doubles with every further retry.
+/// - `max_backoff`: The maximum delay in seconds between two retries.
+/// - `timeout`: The timeout in seconds for connecting to and waiting on the destination.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub count: usize,
+    pub backoff: u64,
+    pub max_backoff: u64,
+    pub timeout: u64,
+}
+
+impl RetryPolicy {
+    /// Creates a new `RetryPolicy` instance by loading values from environment variables.
+    ///
+    /// This method reads the following environment variables:
+    ///
+    /// - `RETRY_COUNT`: How many times a failed operation is retried.
+    /// - `RETRY_BACKOFF_IN_SECONDS`: The delay before the first retry, doubled with every further retry.
+    /// - `RETRY_MAX_BACKOFF_IN_SECONDS`: The maximum delay between two retries.
+    /// - `TIMEOUT_IN_SECONDS`: The timeout for connecting to and waiting on the destination. Must be greater than `0`.
+    ///
+    /// If an environment variable is not set, it will use a default value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the environment variables cannot be parsed, or the timeout is `0`.
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let timeout = Self::parse_env_or_default("TIMEOUT_IN_SECONDS", 60)?;
+        // A zero timeout makes every connection attempt fail, while libssh2 would treat it as no timeout at all
+        if timeout == 0 {
+            return Err("Invalid TIMEOUT_IN_SECONDS '0', expected a number greater than 0.".into());
+        }
+
+        Ok(Self {
+            count: Self::parse_env_or_default("RETRY_COUNT", 3)? as usize,
+            backoff: Self::parse_env_or_default("RETRY_BACKOFF_IN_SECONDS", 5)?,
+            max_backoff: Self::parse_env_or_default("RETRY_MAX_BACKOFF_IN_SECONDS", 300)?,
+            timeout,
+        })
+    }
+
+    /// Returns the delay before the given retry (starting at `1`), doubling with every retry up to `max_backoff`.
+    pub fn backoff_for(&self, retry: usize) -> Duration {
+        let factor = 2u64.saturating_pow(retry.saturating_sub(1) as u32);
+        Duration::from_secs(self.backoff.saturating_mul(factor).min(self.max_backoff))
+    }
+
+    /// Returns the timeout for connecting to and waiting on the destination.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    /// Helper function to parse an environment variable as `u64`, defaulting to the provided value if not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the environment variable is set but not a number.
+    fn parse_env_or_default(var_name: &str, default: u64) -> Result<u64, Box<dyn Error>> {
+        match env::var(var_name) {
+            Ok(val) => Ok(val.parse::<u64>()
+                .map_err(|_| format!("Invalid {} '{}', expected a number.", var_name, val))?),
+            Err(_) => Ok(default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_from_env_rejects_a_zero_or_invalid_timeout() {
+        env::set_var("TIMEOUT_IN_SECONDS", "0");
+        assert!(RetryPolicy::new_from_env().is_err());
+        env::set_var("TIMEOUT_IN_SECONDS", "soon");
+        assert!(RetryPolicy::new_from_env().is_err());
+        env::set_var("TIMEOUT_IN_SECONDS", "1");
+        assert_eq!(RetryPolicy::new_from_env().unwrap().timeout(), Duration::from_secs(1));
+        env::remove_var("TIMEOUT_IN_SECONDS");
+    }
+}
@@ -0,0 +1,63 @@
+use std::env;
+use std::error::Error;
+
+/// A struct to hold the configuration of an S3-compatible object storage destination.
+///
+/// The `S3Config` struct contains the following fields:
+///
+/// - `endpoint`: The URL of the S3 API, e.g. `https://s3.eu-central-1.amazonaws.com` or `http://minio:9000`.
+/// - `bucket`: The name of the bucket where backups are stored.
+/// - `prefix`: The key prefix (folder) inside the bucket, without leading or trailing slashes.
+/// - `region`: The region used for signing requests.
+/// - `access_key_id`: The access key ID used for authentication.
+/// - `secret_access_key`: The secret access key used for authentication.
+/// - `path_style`: Whether to address the bucket in the path (`endpoint/bucket/key`) instead of
+///   the host name (`bucket.endpoint/key`). Most MinIO and Garage setups require path-style addressing.
+#[derive(Clone)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub prefix: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub path_style: bool,
+}
+
+impl S3Config {
+    /// Creates a new `S3Config` instance by loading values from environment variables.
+    ///
+    /// This method reads the following environment variables:
+    ///
+    /// - `S3_BUCKET`: The name of the bucket where backups are stored.
+    /// - `S3_ACCESS_KEY_ID`: The access key ID used for authentication.
+    /// - `S3_SECRET_ACCESS_KEY`: The secret access key used for authentication.
+    /// - `S3_REGION`: Optional. The region of the bucket, defaults to `us-east-1`.
+    /// - `S3_ENDPOINT`: Optional. The URL of the S3 API, defaults to the AWS endpoint of the region.
+    /// - `S3_PREFIX`: Optional. The key prefix inside the bucket, defaults to the bucket root.
+    /// - `S3_PATH_STYLE`: Optional. Set to `true` to use path-style addressing, defaults to `false`.
+    ///
+    /// # Arguments
+    ///
+    /// * `env_prefix` - The prefix of all environment variables, e.g. `NAS_` for a destination named `nas`,
+    ///   or an empty string for a single unnamed destination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if any of the required environment variables are not set or cannot be read.
+    pub fn new_from_env(env_prefix: &str) -> Result<Self, Box<dyn Error>> {
+        let var = |name: &str| env::var(format!("{}{}", env_prefix, name));
+        let bucket = var("S3_BUCKET")?;
+        let access_key_id = var("S3_ACCESS_KEY_ID")?;
+        let secret_access_key = var("S3_SECRET_ACCESS_KEY")?;
+        let region = var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let endpoint = var("S3_ENDPOINT")
+            .unwrap_or_else(|_| format!("https://s3.{}.amazonaws.com", region))
+            .trim_end_matches('/')
+            .to_string();
+        let prefix = var("S3_PREFIX").unwrap_or_default().trim_matches('/').to_string();
+        let path_style = var("S3_PATH_STYLE").map(|val| val == "true").unwrap_or(false);
+
+        Ok(Self { endpoint, bucket, prefix, region, access_key_id, secret_access_key, path_style })
+    }
+}
@@ -1,5 +1,11 @@
+use crate::utility::configs::jump_host_config::JumpHostConfig;
 use std::env;
 use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// The private keys that are used, in this order, if `SSH_KEY_PATH` is not set.
+const DEFAULT_SSH_KEY_PATHS: [&str; 3] = [".ssh/id_ed25519", ".ssh/id_ecdsa", ".ssh/id_rsa"];
 
 /// A struct to hold server configuration parameters.
 ///
@@ -9,14 +15,31 @@ use std::error::Error;
 /// - `server_port`: The port on which the server is running.
 /// - `server_user`: The username for accessing the server.
 /// - `server_directory`: The directory on the server where backups are stored.
-/// - `ssh_key_path`: The path to the SSH private key used for authenticating to the server.
+/// - `ssh_key_path`: The path to the SSH private key (RSA, ECDSA or Ed25519) used for authenticating to the server.
+/// - `ssh_key`: The SSH private key itself, used instead of `ssh_key_path` if set.
+/// - `ssh_key_passphrase`: The passphrase of an encrypted SSH private key.
+/// - `server_password`: The password used for authenticating to the server if key authentication is not possible.
+/// - `use_ssh_agent`: Whether to authenticate with the identities of the ssh-agent listening on `SSH_AUTH_SOCK`.
+/// - `known_hosts_path`: The path to the `known_hosts` file used for verifying the server's host key.
+/// - `host_key_fingerprint`: An optional pinned SHA256 fingerprint of the server's host key, e.g.
+///   `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`. If set, it is used instead of the `known_hosts` file.
+/// - `trust_on_first_use`: Whether an unknown host key is accepted and added to the `known_hosts` file.
+/// - `jump_hosts`: The jump hosts (bastions) the server is reached through, in the order they are passed through.
 #[derive(Clone)]
 pub struct ServerConfig {
     pub server_ip: String,
     pub server_port: String,
     pub server_user: String,
     pub server_directory: String,
-    pub ssh_key_path: String,
+    pub ssh_key_path: Option<String>,
+    pub ssh_key: Option<String>,
+    pub ssh_key_passphrase: Option<String>,
+    pub server_password: Option<String>,
+    pub use_ssh_agent: bool,
+    pub known_hosts_path: String,
+    pub host_key_fingerprint: Option<String>,
+    pub trust_on_first_use: bool,
+    pub jump_hosts: Vec<JumpHostConfig>,
 }
 
 impl ServerConfig {
@@ -28,23 +51,77 @@ impl ServerConfig {
     /// - `SERVER_PORT`: The port on which the server is running.
     /// - `SERVER_USER`: The username for accessing the server.
     /// - `SERVER_DIRECTORY`: The directory on the server where backups are stored.
+    /// - `SSH_KEY_PATH`: Optional. The path to the SSH private key, e.g. a Docker secret like `/run/secrets/ssh_key`.
+    ///   Defaults to the first existing key of `.ssh/id_ed25519`, `.ssh/id_ecdsa` and `.ssh/id_rsa`.
+    /// - `SSH_KEY`: Optional. The SSH private key itself, with line breaks optionally escaped as `\n`.
+    /// - `SSH_KEY_PASSPHRASE` or `SSH_KEY_PASSPHRASE_FILE`: Optional. The passphrase of an encrypted SSH private key.
+    /// - `SERVER_PASSWORD` or `SERVER_PASSWORD_FILE`: Optional. The password for accessing the server.
+    /// - `SSH_AUTH_SOCK`: Optional. The socket of an ssh-agent whose identities are used for authentication.
+    /// - `SSH_KNOWN_HOSTS_PATH`: Optional. The path to the `known_hosts` file, defaults to `.ssh/known_hosts`.
+    /// - `SSH_HOST_KEY_FINGERPRINT`: Optional. The pinned SHA256 fingerprint of the server's host key.
+    /// - `SSH_TRUST_ON_FIRST_USE`: Optional. Set to `true` to accept and remember an unknown host key.
+    /// - `SSH_JUMP_HOSTS`: Optional. The jump hosts the server is reached through, see `JumpHostConfig::new_all_from_env`.
     ///
-    /// The `ssh_key_path` must be provided as a parameter.
+    /// The `*_FILE` variants read the secret from a file, e.g. a Docker secret mounted at `/run/secrets`.
     ///
     /// # Arguments
     ///
-    /// * `ssh_key_path` - The path to the SSH private key used for authenticating to the server.
+    /// * `env_prefix` - The prefix of all environment variables, e.g. `NAS_` for a destination named `nas`,
+    ///   or an empty string for a single unnamed destination.
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if any of the environment variables are not set or cannot be read.
+    /// Returns an `Err` if any of the required environment variables are not set or cannot be read.
     ///
-    pub fn new_from_env(ssh_key_path: String) -> Result<Self, Box<dyn Error>> {
-        let server_ip = env::var("SERVER_IP")?;
-        let server_port = env::var("SERVER_PORT")?;
-        let server_user = env::var("SERVER_USER")?;
-        let server_directory = env::var("SERVER_DIRECTORY")?;
+    pub fn new_from_env(env_prefix: &str) -> Result<Self, Box<dyn Error>> {
+        let var = |name: &str| env::var(format!("{}{}", env_prefix, name));
+        let server_ip = var("SERVER_IP")?;
+        let server_port = var("SERVER_PORT")?;
+        let server_user = var("SERVER_USER")?;
+        let server_directory = var("SERVER_DIRECTORY")?;
+        let ssh_key_path = var("SSH_KEY_PATH").ok().or_else(|| DEFAULT_SSH_KEY_PATHS.iter()
+            .find(|path| Path::new(path).exists())
+            .map(|path| path.to_string()));
+        let ssh_key = Self::ssh_key_from_env(&format!("{}SSH_KEY", env_prefix));
+        let ssh_key_passphrase = Self::secret_from_env(&format!("{}SSH_KEY_PASSPHRASE", env_prefix))?;
+        let server_password = Self::secret_from_env(&format!("{}SERVER_PASSWORD", env_prefix))?;
+        let use_ssh_agent = env::var("SSH_AUTH_SOCK").is_ok();
+        let known_hosts_path = var("SSH_KNOWN_HOSTS_PATH").unwrap_or_else(|_| ".ssh/known_hosts".to_string());
+        let host_key_fingerprint = var("SSH_HOST_KEY_FINGERPRINT").ok();
+        let trust_on_first_use = var("SSH_TRUST_ON_FIRST_USE").map(|val| val == "true").unwrap_or(false);
+        let jump_hosts = JumpHostConfig::new_all_from_env(env_prefix, &server_user)?;
+
+        Ok(Self {
+            server_ip,
+            server_port,
+            server_user,
+            server_directory,
+            ssh_key_path,
+            ssh_key,
+            ssh_key_passphrase,
+            server_password,
+            use_ssh_agent,
+            known_hosts_path,
+            host_key_fingerprint,
+            trust_on_first_use,
+            jump_hosts,
+        })
+    }
+
+    /// Helper function to read an SSH private key from the environment variable `var_name`. Returns `None` if it is not set.
+    pub fn ssh_key_from_env(var_name: &str) -> Option<String> {
+        // Env files can't hold multi-line values, so escaped line breaks are accepted as well
+        env::var(var_name).ok().map(|key| key.replace("\\n", "\n"))
+    }
+
+    /// Helper function to read a secret from the environment variable `var_name`, or from the file
+    /// referenced by `<var_name>_FILE`. Returns `None` if neither is set.
+    pub fn secret_from_env(var_name: &str) -> Result<Option<String>, Box<dyn Error>> {
+        if let Ok(secret) = env::var(var_name) { return Ok(Some(secret)); }
 
-        Ok(Self { server_ip, server_port, server_user, server_directory, ssh_key_path })
+        match env::var(format!("{}_FILE", var_name)) {
+            Ok(path) => Ok(Some(fs::read_to_string(path)?.trim_end_matches(['\r', '\n']).to_string())),
+            Err(_) => Ok(None),
+        }
     }
 }
@@ -0,0 +1,64 @@
+use crate::utility::configs::local_config::LocalConfig;
+use crate::utility::configs::retry_policy::RetryPolicy;
+use crate::utility::configs::s3_config::S3Config;
+use crate::utility::configs::server_config::ServerConfig;
+use crate::utility::storage::local::LocalStorage;
+use crate::utility::storage::retry::RetryingStorage;
+use crate::utility::storage::s3::S3Storage;
+use crate::utility::storage::server::Server;
+use crate::utility::storage::StorageBackend;
+use std::env;
+use std::error::Error;
+
+/// The destination where backups are stored, selected by the `STORAGE_TYPE` environment variable.
+///
+/// - `Server`: A remote server accessed via SSH (`STORAGE_TYPE=ssh`, the default).
+/// - `Local`: A local directory, e.g. a mounted NFS or CIFS share (`STORAGE_TYPE=local`).
+/// - `S3`: An S3-compatible object storage bucket (`STORAGE_TYPE=s3`).
+#[derive(Clone)]
+pub enum StorageConfig {
+    Server(ServerConfig),
+    Local(LocalConfig),
+    S3(S3Config),
+}
+
+impl StorageConfig {
+    /// Creates a new `StorageConfig` instance by loading values from environment variables.
+    ///
+    /// The `STORAGE_TYPE` environment variable selects the destination. If it is not set,
+    /// the SSH server destination is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `env_prefix` - The prefix of all environment variables, e.g. `NAS_` for a destination named `nas`,
+    ///   or an empty string for a single unnamed destination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if `STORAGE_TYPE` is invalid or the configuration of the selected
+    /// destination cannot be read.
+    pub fn new_from_env(env_prefix: &str) -> Result<Self, Box<dyn Error>> {
+        let var = |name: &str| env::var(format!("{}{}", env_prefix, name));
+        let storage_type = var("STORAGE_TYPE").unwrap_or_else(|_| "ssh".to_string());
+
+        match storage_type.as_str() {
+            "ssh" => Ok(Self::Server(ServerConfig::new_from_env(env_prefix)?)),
+            "local" => Ok(Self::Local(LocalConfig::new_from_env(env_prefix)?)),
+            "s3" => Ok(Self::S3(S3Config::new_from_env(env_prefix)?)),
+            _ => Err(format!("Invalid STORAGE_TYPE '{}' specified. Use 'ssh', 'local' or 's3'.", storage_type).into()),
+        }
+    }
+
+    /// Creates the storage backend described by this configuration.
+    ///
+    /// Every operation of the returned backend is retried according to `retry_policy`.
+    pub fn create_backend(&self, retry_policy: &RetryPolicy) -> Box<dyn StorageBackend> {
+        let backend: Box<dyn StorageBackend> = match self {
+            Self::Server(config) => Box::new(Server::new(config.clone(), retry_policy.timeout())),
+            Self::Local(config) => Box::new(LocalStorage::new(config.clone())),
+            Self::S3(config) => Box::new(S3Storage::new(config.clone(), retry_policy.timeout())),
+        };
+
+        Box::new(RetryingStorage::new(backend, retry_policy.clone()))
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+
+/// Where the Docker daemon stores the data of local volumes on the host.
+const DEFAULT_DOCKER_VOLUMES_PATH: &str = "/var/lib/docker/volumes";
+
+/// How the volumes to be backed up are found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VolumeDiscovery {
+    /// Every folder mounted in `/backup` is a volume named like the folder.
+    Mounts,
+    /// The volumes are listed through the Docker Engine API and selected by label or name.
+    Docker,
+}
+
+/// A struct to hold the configuration of how the volumes to be backed up are found.
+///
+/// - `discovery`: Whether the volumes are the folders in `/backup` or are listed through the Docker Engine API.
+/// - `labels`: The labels selecting volumes, each written as `key` or `key=value`.
+/// - `name_patterns`: The glob patterns selecting volumes by name, e.g. `myapp_*`.
+/// - `volumes_path`: The path where the host's Docker volumes directory is mounted, used to read the data of
+///   discovered volumes.
+#[derive(Clone)]
+pub struct VolumeDiscoveryConfig {
+    pub discovery: VolumeDiscovery,
+    pub labels: Vec<String>,
+    pub name_patterns: Vec<String>,
+    pub volumes_path: String,
+}
+
+impl VolumeDiscoveryConfig {
+    /// Creates a new `VolumeDiscoveryConfig` instance by loading values from environment variables.
+    ///
+    /// This method reads the following environment variables:
+    ///
+    /// - `VOLUME_DISCOVERY`: Optional. `mounts` to back up the folders in `/backup`, or `docker` to discover the
+    ///   volumes through the Docker Engine API. Defaults to `mounts`.
+    /// - `VOLUME_LABELS`: Optional. A comma-separated list of labels selecting volumes, e.g. `backup.enable=true`.
+    /// - `VOLUME_NAME_PATTERNS`: Optional. A comma-separated list of glob patterns selecting volumes by name,
+    ///   e.g. `myapp_*,db?`.
+    /// - `DOCKER_VOLUMES_PATH`: Optional. Where the host's `/var/lib/docker/volumes` is mounted. Defaults to
+    ///   `/var/lib/docker/volumes`.
+    ///
+    /// A volume is selected if it matches any of the labels or patterns. If neither is set, volumes labelled
+    /// `backup.enable=true` are selected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if `VOLUME_DISCOVERY` is unknown.
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let discovery = match env::var("VOLUME_DISCOVERY").map(|discovery| discovery.trim().to_lowercase()) {
+            Ok(discovery) if discovery == "mounts" => VolumeDiscovery::Mounts,
+            Ok(discovery) if discovery == "docker" => VolumeDiscovery::Docker,
+            Ok(discovery) => return Err(format!("Unknown VOLUME_DISCOVERY '{}'. Use 'mounts' or 'docker'.", discovery).into()),
+            Err(_) => VolumeDiscovery::Mounts,
+        };
+
+        let list_from_env = |name: &str| -> Vec<String> {
+            env::var(name).unwrap_or_default()
+                .split(',')
+                .map(|entry| entry.trim().to_string())
+                .filter(|entry| !entry.is_empty())
+                .collect()
+        };
+        let mut labels = list_from_env("VOLUME_LABELS");
+        let name_patterns = list_from_env("VOLUME_NAME_PATTERNS");
+        if labels.is_empty() && name_patterns.is_empty() {
+            labels.push("backup.enable=true".to_string());
+        }
+
+        let volumes_path = env::var("DOCKER_VOLUMES_PATH")
+            .map(|path| path.trim_end_matches('/').to_string())
+            .unwrap_or_else(|_| DEFAULT_DOCKER_VOLUMES_PATH.to_string());
+
+        Ok(Self { discovery, labels, name_patterns, volumes_path })
+    }
+
+    /// Returns whether a volume discovered through the Docker Engine API is selected for the backup.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the volume.
+    /// * `volume_labels` - The labels of the volume.
+    pub fn selects(&self, name: &str, volume_labels: &HashMap<String, String>) -> bool {
+        let matches_label = self.labels.iter().any(|label| match label.split_once('=') {
+            Some((key, value)) => volume_labels.get(key).is_some_and(|actual| actual == value),
+            None => volume_labels.contains_key(label),
+        });
+
+        matches_label || self.name_patterns.iter().any(|pattern| matches_glob(pattern, name))
+    }
+
+    /// Translates the mountpoint of a volume on the host into the path where its data can be read.
+    ///
+    /// # Arguments
+    ///
+    /// * `mountpoint` - The mountpoint of the volume as reported by the Docker daemon,
+    ///   e.g. `/var/lib/docker/volumes/my_volume/_data`.
+    pub fn data_path(&self, mountpoint: &str) -> String {
+        match mountpoint.strip_prefix(DEFAULT_DOCKER_VOLUMES_PATH) {
+            Some(relative_path) => format!("{}{}", self.volumes_path, relative_path),
+            None => mountpoint.to_string(),
+        }
+    }
+}
+
+/// Returns whether a name matches a glob pattern, where `*` matches any number of characters and `?` a single one.
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Backtrack to the last `*` whenever the rest of the pattern does not match
+    let (mut p, mut n) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            last_star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = last_star {
+            p = star_p + 1;
+            n = star_n + 1;
+            last_star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_glob_handles_wildcards() {
+        assert!(matches_glob("myapp_*", "myapp_db"));
+        assert!(matches_glob("myapp_*", "myapp_"));
+        assert!(matches_glob("db?", "db1"));
+        assert!(matches_glob("*_data", "redis_cache_data"));
+        assert!(matches_glob("*a*b*", "xxaxxbxx"));
+        assert!(matches_glob("**", ""));
+        assert!(matches_glob("exact", "exact"));
+
+        assert!(!matches_glob("db?", "db"));
+        assert!(!matches_glob("db?", "db12"));
+        assert!(!matches_glob("myapp_*", "other_myapp_db"));
+        assert!(!matches_glob("*_data", "redis_data_old"));
+        assert!(!matches_glob("exact", "exactly"));
+        assert!(!matches_glob("", "volume"));
+    }
+
+    #[test]
+    fn matches_glob_backtracks_to_the_last_star() {
+        assert!(matches_glob("*ab", "aab"));
+        assert!(matches_glob("a*b*c", "abbbcbc"));
+        assert!(!matches_glob("a*b*c", "abbbcb"));
+    }
+}
@@ -0,0 +1,358 @@
+use crate::utility::configs::docker_config::DockerConfig;
+use crate::utility::docker::{get_container_consistency_mode, get_container_dependencies, start_containers, stop_containers_by_id, wait_until_healthy, ConsistencyMode};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::collections::HashMap;
+use std::error::Error;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// A container suspended by a `ContainerGuard`.
+///
+/// `pending` is set while the container is being suspended, so it may or may not be suspended yet.
+struct SuspendedContainer {
+    docker_config: DockerConfig,
+    id: String,
+    mode: ConsistencyMode,
+    pending: bool,
+}
+
+/// All containers currently suspended by any guard, so they can be resumed when the process is terminated.
+static SUSPENDED_CONTAINERS: Mutex<Vec<SuspendedContainer>> = Mutex::new(Vec::new());
+
+/// Set once the process received SIGTERM or SIGINT, after which no further containers are suspended.
+static TERMINATING: AtomicBool = AtomicBool::new(false);
+
+/// Records the containers suspended during a backup or restore and guarantees that they are resumed.
+///
+/// Containers are resumed when `resume` or `resume_all` is called, when the guard is dropped
+/// (including while unwinding from a panic), or when the process receives SIGTERM or SIGINT
+/// after `resume_containers_on_termination` was called.
+///
+/// The guard respects the dependencies between containers declared with Docker Compose's `depends_on`
+/// or with links: containers are suspended before the containers they depend on, and resumed after them.
+/// Only the containers of the same guard are ordered. A backup uses one guard per group of volumes, so
+/// dependencies on containers of other groups, which may be handled in parallel, are not respected.
+pub struct ContainerGuard<'a> {
+    docker_config: &'a DockerConfig,
+    containers: Vec<(String, ConsistencyMode)>,
+    dependencies: HashMap<String, Vec<String>>,
+    deferred: Vec<String>,
+}
+
+impl<'a> ContainerGuard<'a> {
+    /// Creates a guard without any suspended containers.
+    pub fn new(docker_config: &'a DockerConfig) -> Self {
+        Self { docker_config, containers: Vec::new(), dependencies: HashMap::new(), deferred: Vec::new() }
+    }
+
+    /// Returns whether the guard holds a container suspended.
+    pub fn contains(&self, container_id: &str) -> bool {
+        self.containers.iter().any(|(id, _)| id == container_id)
+    }
+
+    /// Returns the IDs of the containers the guard holds suspended.
+    pub fn container_ids(&self) -> Vec<String> {
+        self.containers.iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    /// Suspends containers and records them, so they are resumed with the same mode later.
+    ///
+    /// Containers the guard already holds suspended are skipped. Containers are suspended before the
+    /// containers they depend on. Every container is recorded right before it is suspended, so the
+    /// containers suspended before a failure, or while suspending failed, are resumed as well.
+    ///
+    /// A container can override how it is suspended with its `backup.mode` or `backup.stop` labels.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_ids` - The IDs of the containers to suspend.
+    /// * `mode_of` - Returns how a container is suspended unless its labels say otherwise.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error if a container could not be suspended.
+    pub fn suspend(&mut self,
+                   container_ids: &[String],
+                   mode_of: impl Fn(&str) -> ConsistencyMode) -> Result<(), Box<dyn Error>> {
+        self.suspend_containers(container_ids, mode_of, true)
+    }
+
+    /// Stops containers and records them, ignoring their `backup.mode` and `backup.stop` labels.
+    ///
+    /// The labels only decide how consistent a backup is, so they must not keep a container running
+    /// while its volume is overwritten, e.g. by a restore.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_ids` - The IDs of the containers to stop.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error if a container could not be stopped.
+    pub fn stop(&mut self, container_ids: &[String]) -> Result<(), Box<dyn Error>> {
+        self.suspend_containers(container_ids, |_| ConsistencyMode::Stop, false)
+    }
+
+    /// Suspends containers in the order of their dependencies and records them.
+    ///
+    /// If `honour_labels` is set, the `backup.mode` and `backup.stop` labels of a container override `mode_of`.
+    fn suspend_containers(&mut self,
+                          container_ids: &[String],
+                          mode_of: impl Fn(&str) -> ConsistencyMode,
+                          honour_labels: bool) -> Result<(), Box<dyn Error>> {
+        let mut new_container_ids: Vec<String> = Vec::new();
+        let mut label_modes: HashMap<String, ConsistencyMode> = HashMap::new();
+        for container_id in container_ids {
+            if self.contains(container_id) || new_container_ids.contains(container_id) { continue; }
+            if !self.dependencies.contains_key(container_id) {
+                let dependencies = get_container_dependencies(self.docker_config, container_id)?;
+                self.dependencies.insert(container_id.clone(), dependencies);
+            }
+            if honour_labels {
+                if let Some(mode) = get_container_consistency_mode(self.docker_config, container_id)? {
+                    label_modes.insert(container_id.clone(), mode);
+                }
+            }
+            new_container_ids.push(container_id.clone());
+        }
+
+        for container_id in self.dependency_tiers(&new_container_ids).into_iter().rev().flatten() {
+            let mode = label_modes.get(&container_id).copied().unwrap_or_else(|| mode_of(&container_id));
+
+            // Record the container as pending before suspending it, so the signal handler resumes it
+            // even if it is terminated while the container is being suspended
+            {
+                let mut suspended_containers = lock_suspended_containers();
+                if TERMINATING.load(Ordering::SeqCst) {
+                    return Err("The process is terminating, no further containers are suspended.".into());
+                }
+                suspended_containers.push(SuspendedContainer {
+                    docker_config: self.docker_config.clone(),
+                    id: container_id.clone(),
+                    mode,
+                    pending: true,
+                });
+            }
+            self.containers.push((container_id.clone(), mode));
+            stop_containers_by_id(self.docker_config, std::slice::from_ref(&container_id), mode)?;
+
+            if let Some(container) = lock_suspended_containers().iter_mut().find(|container| container.id == container_id) {
+                container.pending = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resumes containers the guard holds suspended, the way they were suspended.
+    ///
+    /// Containers are resumed after the containers they depend on, waiting `dependency_wait` of the
+    /// Docker configuration in between. If `health_timeout` is set, every resumed container must also
+    /// become healthy before the containers depending on it are resumed. A container depending on a
+    /// container that stays suspended is only resumed together with that container. All given containers
+    /// are resumed even if some of them fail, and containers the guard does not hold suspended are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_ids` - The IDs of the containers to resume.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error listing the containers
+    ///   that could not be resumed or did not become healthy.
+    pub fn resume(&mut self, container_ids: &[String]) -> Result<(), Box<dyn Error>> {
+        for container_id in container_ids {
+            if self.contains(container_id) && !self.deferred.contains(container_id) {
+                self.deferred.push(container_id.clone());
+            }
+        }
+
+        // Keep the containers suspended whose dependencies stay suspended
+        let mut ready = self.deferred.clone();
+        loop {
+            let blocked: Vec<String> = ready.iter()
+                .filter(|container_id| self.dependencies_of(container_id)
+                    .any(|dependency| self.contains(dependency) && !ready.contains(dependency)))
+                .cloned()
+                .collect();
+            if blocked.is_empty() { break; }
+            ready.retain(|container_id| !blocked.contains(container_id));
+        }
+        self.deferred.retain(|container_id| !ready.contains(container_id));
+
+        let mut errors = Vec::new();
+        for (tier_index, tier) in self.dependency_tiers(&ready).into_iter().enumerate() {
+            if tier_index > 0 && self.docker_config.dependency_wait > 0 {
+                thread::sleep(self.docker_config.dependency_wait());
+            }
+
+            let mut resumed_container_ids = Vec::new();
+            for container_id in tier {
+                let position = self.containers.iter().position(|(id, _)| *id == container_id);
+                let Some((container_id, mode)) = position.map(|position| self.containers.remove(position)) else { continue };
+                lock_suspended_containers().retain(|container| container.id != container_id);
+                match start_containers(self.docker_config, vec![container_id.clone()], mode) {
+                    Ok(()) if mode != ConsistencyMode::None => resumed_container_ids.push(container_id),
+                    Ok(()) => {}
+                    Err(e) => errors.push(e.to_string()),
+                }
+            }
+
+            if let Some(health_timeout) = self.docker_config.health_timeout {
+                for container_id in resumed_container_ids {
+                    if let Err(e) = wait_until_healthy(self.docker_config, &container_id, Duration::from_secs(health_timeout)) {
+                        errors.push(e.to_string());
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors.join(" ").into()) }
+    }
+
+    /// Resumes all containers the guard holds suspended.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error listing the containers
+    ///   that could not be resumed.
+    pub fn resume_all(&mut self) -> Result<(), Box<dyn Error>> {
+        self.resume(&self.container_ids())
+    }
+
+    /// Returns the IDs of the containers a container depends on.
+    fn dependencies_of(&self, container_id: &str) -> impl Iterator<Item = &String> {
+        self.dependencies.get(container_id).into_iter().flatten()
+    }
+
+    /// Splits containers into tiers, so that every container only depends on containers in earlier tiers.
+    ///
+    /// Dependencies on containers that are not given are ignored, and containers depending on each other
+    /// in a cycle are put into the same tier.
+    fn dependency_tiers(&self, container_ids: &[String]) -> Vec<Vec<String>> {
+        let mut remaining = container_ids.to_vec();
+        let mut tiers = Vec::new();
+
+        while !remaining.is_empty() {
+            let tier: Vec<String> = remaining.iter()
+                .filter(|container_id| !self.dependencies_of(container_id).any(|dependency| remaining.contains(dependency)))
+                .cloned()
+                .collect();
+            let tier = if tier.is_empty() { std::mem::take(&mut remaining) } else { tier };
+            remaining.retain(|container_id| !tier.contains(container_id));
+            tiers.push(tier);
+        }
+        tiers
+    }
+}
+
+impl Drop for ContainerGuard<'_> {
+    fn drop(&mut self) {
+        if self.containers.is_empty() { return; }
+
+        println!("Restarting {} container(s) left suspended by a failed operation.", self.containers.len());
+        if let Err(e) = self.resume_all() {
+            println!("Failed to restart containers: {}", e);
+        }
+    }
+}
+
+/// Resumes all suspended containers and exits when the process receives SIGTERM or SIGINT.
+///
+/// Without this, stopping the backup container (e.g. with `docker stop`) while a volume is being
+/// backed up would leave the containers using it stopped. The signals are handled on a separate thread.
+///
+/// Once a signal is received, no further containers are suspended. Containers that were still being
+/// suspended are resumed as well, which does nothing if they are still running.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error if the signal handler cannot be registered.
+pub fn resume_containers_on_termination() -> Result<(), Box<dyn Error>> {
+    let mut signals = Signals::new([SIGTERM, SIGINT])?;
+
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            let suspended_containers = {
+                let mut suspended_containers = lock_suspended_containers();
+                TERMINATING.store(true, Ordering::SeqCst);
+                std::mem::take(&mut *suspended_containers)
+            };
+            if !suspended_containers.is_empty() {
+                println!("Received signal {}, restarting {} suspended container(s).", signal, suspended_containers.len());
+            }
+
+            // The containers were suspended before their dependencies, so they are resumed in reverse
+            for container in suspended_containers.into_iter().rev() {
+                match start_containers(&container.docker_config, vec![container.id], container.mode) {
+                    // A pending container may not have been suspended yet
+                    Err(_) if container.pending => {}
+                    Err(e) => println!("Failed to restart container: {}", e),
+                    Ok(()) => {}
+                }
+            }
+            process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+/// Locks the list of suspended containers, even if a thread panicked while holding the lock.
+fn lock_suspended_containers() -> std::sync::MutexGuard<'static, Vec<SuspendedContainer>> {
+    SUSPENDED_CONTAINERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn docker_config() -> DockerConfig {
+        DockerConfig {
+            socket_path: "/nonexistent/docker.sock".to_string(),
+            timeout: 1,
+            dependency_wait: 0,
+            health_timeout: None,
+            health_min_running: 0,
+        }
+    }
+
+    fn ids(container_ids: &[&str]) -> Vec<String> {
+        container_ids.iter().map(|container_id| container_id.to_string()).collect()
+    }
+
+    #[test]
+    fn dependency_tiers_put_dependencies_first() {
+        let docker_config = docker_config();
+        let mut guard = ContainerGuard::new(&docker_config);
+        guard.dependencies.insert("app".to_string(), ids(&["db", "cache"]));
+        guard.dependencies.insert("proxy".to_string(), ids(&["app"]));
+        guard.dependencies.insert("cache".to_string(), ids(&["db"]));
+
+        let tiers = guard.dependency_tiers(&ids(&["proxy", "app", "cache", "db", "worker"]));
+        assert_eq!(tiers, vec![ids(&["db", "worker"]), ids(&["cache"]), ids(&["app"]), ids(&["proxy"])]);
+    }
+
+    #[test]
+    fn dependency_tiers_ignore_containers_that_are_not_given() {
+        let docker_config = docker_config();
+        let mut guard = ContainerGuard::new(&docker_config);
+        guard.dependencies.insert("app".to_string(), ids(&["db"]));
+
+        assert_eq!(guard.dependency_tiers(&ids(&["app"])), vec![ids(&["app"])]);
+        assert!(guard.dependency_tiers(&[]).is_empty());
+    }
+
+    #[test]
+    fn dependency_tiers_keep_cycles_together() {
+        let docker_config = docker_config();
+        let mut guard = ContainerGuard::new(&docker_config);
+        guard.dependencies.insert("a".to_string(), ids(&["b"]));
+        guard.dependencies.insert("b".to_string(), ids(&["a"]));
+        guard.dependencies.insert("c".to_string(), ids(&["a"]));
+
+        assert_eq!(guard.dependency_tiers(&ids(&["a", "b", "c", "d"])), vec![ids(&["d"]), ids(&["a", "b", "c"])]);
+    }
+}
@@ -1,76 +1,704 @@
+use crate::utility::configs::docker_config::DockerConfig;
+use crate::utility::uri::uri_encode;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
-use std::process::Command;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How the containers using a volume are kept from changing it while it is backed up.
+///
+/// The variants are ordered from the weakest to the strongest mode, so that a container using
+/// volumes with different modes can be suspended with the strongest of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConsistencyMode {
+    /// The containers keep running, e.g. for read-only or append-only data.
+    None,
+    /// The containers are frozen with `docker pause`, which is much cheaper than a full stop/start cycle.
+    Pause,
+    /// The containers are stopped with `docker stop`.
+    Stop,
+}
+
+impl ConsistencyMode {
+    /// Parses the name of a consistency mode as used in the configuration, e.g. `pause`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the name does not belong to any mode.
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "stop" => Ok(ConsistencyMode::Stop),
+            "pause" => Ok(ConsistencyMode::Pause),
+            "none" => Ok(ConsistencyMode::None),
+            _ => Err(format!("Unknown consistency mode '{}'. Use 'stop', 'pause' or 'none'.", name).into()),
+        }
+    }
+}
+
+/// A Docker volume as reported by the Docker Engine API.
+///
+/// - `name`: The name of the volume.
+/// - `mountpoint`: The path of the volume's data on the host.
+/// - `labels`: The labels of the volume.
+pub struct DockerVolume {
+    pub name: String,
+    pub mountpoint: String,
+    pub labels: HashMap<String, String>,
+}
+
+impl DockerVolume {
+    /// Creates a `DockerVolume` from a volume object returned by the Docker Engine API.
+    fn from_json(volume: &Value) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            name: volume["Name"].as_str().ok_or("The Docker daemon returned a volume without a name.")?.to_string(),
+            mountpoint: volume["Mountpoint"].as_str().unwrap_or_default().to_string(),
+            labels: volume["Labels"].as_object().into_iter().flatten()
+                .filter_map(|(key, value)| value.as_str().map(|value| (key.clone(), value.to_string())))
+                .collect(),
+        })
+    }
+}
 
 /// Starts a Docker containers by their container IDs.
 ///
-/// This function takes a vector of Docker container IDs and starts each container using
-/// the `docker start` command. If the start command fails for any container, an error is returned.
+/// This function takes a vector of Docker container IDs and resumes each container the way it was
+/// suspended: stopped containers are started, paused containers are unpaused. If the Docker daemon
+/// rejects the request for any container, an error is returned.
 ///
 /// # Arguments
 ///
+/// * `docker_config` - The connection to the Docker daemon.
 /// * `container_ids` - A vector of strings representing the IDs of the containers to start.
+/// * `mode` - The consistency mode the containers were suspended with.
 ///
 /// # Returns
 ///
 /// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error if something goes wrong.
-pub fn start_containers(container_ids: Vec<String>) -> Result<(), Box<dyn Error>> {
+pub fn start_containers(docker_config: &DockerConfig,
+                        container_ids: Vec<String>,
+                        mode: ConsistencyMode) -> Result<(), Box<dyn Error>> {
+    let action = match mode {
+        ConsistencyMode::Stop => "start",
+        ConsistencyMode::Pause => "unpause",
+        ConsistencyMode::None => return Ok(()),
+    };
+
     for container_id in container_ids {
-        Command::new("docker")
-            .arg("start")
-            .arg(container_id)
-            .output()?;
+        post_container_action(docker_config, &container_id, action)?;
     }
     Ok(())
 }
 
-/// Stops all Docker containers using a specific volume, excluding the container running this function.
+/// Suspends Docker containers by their container IDs, according to the consistency mode.
 ///
-/// This function retrieves the list of container IDs that are using a specified Docker volume
-/// and stops each of them using the `docker stop` command. It also excludes the container
-/// that is executing this function from being stopped.
+/// Containers are stopped, paused using the cgroup freezer, or left running, and can be resumed
+/// with `start_containers` using the same mode.
 ///
 /// # Arguments
 ///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `container_ids` - A slice of strings representing the IDs of the containers to stop.
+/// * `mode` - The consistency mode defining how the containers are suspended.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>, Box<dyn Error>>` - A vector of strings containing the IDs of the suspended containers, or an error if something goes wrong.
+pub fn stop_containers_by_id(docker_config: &DockerConfig,
+                             container_ids: &[String],
+                             mode: ConsistencyMode) -> Result<Vec<String>, Box<dyn Error>> {
+    let action = match mode {
+        ConsistencyMode::Stop => "stop",
+        ConsistencyMode::Pause => "pause",
+        ConsistencyMode::None => return Ok(container_ids.to_vec()),
+    };
+
+    for container_id in container_ids {
+        post_container_action(docker_config, container_id, action)?;
+    }
+
+    Ok(container_ids.to_vec())
+}
+
+/// Retrieves the IDs of all running Docker containers using a specific volume, excluding the container running this function.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
 /// * `volume` - A string slice representing the name of the Docker volume used as a filter to find containers.
 ///
 /// # Returns
 ///
-/// * `Result<Vec<String>, Box<dyn Error>>` - A vector of strings containing the IDs of the stopped containers, or an error if something goes wrong.
-pub fn stop_containers(volume: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let output = Command::new("docker")
-        .arg("ps")
-        .arg("-q")
-        .arg("--filter")
-        .arg(format!("volume={}", volume))
-        .output()?;
+/// * `Result<Vec<String>, Box<dyn Error>>` - A vector of strings containing the IDs of the containers, or an error if something goes wrong.
+pub fn get_container_ids(docker_config: &DockerConfig, volume: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let container_ids = list_container_ids(docker_config, serde_json::json!({ "volume": [volume] }))
+        .map_err(|e| format!("Failed to list the containers using the volume {}: {}", volume, e))?;
+    let my_container_id = get_my_container_id()?;
 
-    let containers = String::from_utf8(output.stdout)?;
-    let mut container_ids: Vec<String> = Vec::new();
+    Ok(container_ids.into_iter()
+        .filter(|container_id| !my_container_id.as_deref().is_some_and(|my_id| is_same_container(container_id, my_id)))
+        .collect())
+}
 
-    for container_id in containers.trim().split('\n') {
-        if container_id.is_empty() || container_id == get_my_container_id()? { continue; }
+/// Retrieves the IDs of the running containers a container depends on.
+///
+/// The dependencies are read from the `depends_on` section of Docker Compose, which Compose stores
+/// in the `com.docker.compose.depends_on` label of the container, and from legacy container links.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `container_id` - The ID of the container.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>, Box<dyn Error>>` - The IDs of the running containers the container depends on,
+///   or an error if something goes wrong.
+pub fn get_container_dependencies(docker_config: &DockerConfig, container_id: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let container = inspect_container(docker_config, container_id)?;
+    let labels = &container["Config"]["Labels"];
+    let mut dependencies = Vec::new();
 
-        Command::new("docker")
-            .arg("stop")
-            .arg(container_id)
-            .output()?;
+    // Compose writes the dependencies as "service:condition[:restart]", separated by commas
+    if let (Some(project), Some(depends_on)) = (labels["com.docker.compose.project"].as_str(),
+                                                labels["com.docker.compose.depends_on"].as_str()) {
+        for service in depends_on.split(',').filter_map(|dependency| dependency.split(':').next()) {
+            if service.trim().is_empty() { continue; }
+            let filters = serde_json::json!({ "label": [
+                format!("com.docker.compose.project={}", project),
+                format!("com.docker.compose.service={}", service.trim()),
+            ] });
+            dependencies.extend(list_container_ids(docker_config, filters)?);
+        }
+    }
 
-        container_ids.push(container_id.to_string());
+    // Links are written as "/<linked container>:/<container>/<alias>"
+    for link in container["HostConfig"]["Links"].as_array().into_iter().flatten().filter_map(|link| link.as_str()) {
+        let linked_container = link.split(':').next().unwrap_or_default().trim_start_matches('/');
+        if let Some(linked_container_id) = inspect_container(docker_config, linked_container)?["Id"].as_str() {
+            dependencies.push(linked_container_id.to_string());
+        }
+    }
+
+    dependencies.retain(|dependency| dependency != container_id);
+    dependencies.sort();
+    dependencies.dedup();
+    Ok(dependencies)
+}
+
+/// Retrieves all volumes known to the Docker daemon.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+///
+/// # Returns
+///
+/// * `Result<Vec<DockerVolume>, Box<dyn Error>>` - The volumes, or an error if something goes wrong.
+pub fn list_volumes(docker_config: &DockerConfig) -> Result<Vec<DockerVolume>, Box<dyn Error>> {
+    let response = request(docker_config, "GET", "/volumes")?;
+    if response.status != 200 {
+        return Err(format!("Failed to list the volumes: {}", response.error_message()).into());
     }
 
-    Ok(container_ids)
+    let body: Value = serde_json::from_slice(&response.body)?;
+    body["Volumes"].as_array().into_iter().flatten()
+        .map(DockerVolume::from_json)
+        .collect()
+}
+
+/// Retrieves a single volume by its name.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `name` - The name of the volume.
+///
+/// # Returns
+///
+/// * `Result<DockerVolume, Box<dyn Error>>` - The volume, or an error if it does not exist or something goes wrong.
+pub fn inspect_volume(docker_config: &DockerConfig, name: &str) -> Result<DockerVolume, Box<dyn Error>> {
+    let response = request(docker_config, "GET", &format!("/volumes/{}", uri_encode(name)))?;
+    if response.status != 200 {
+        return Err(format!("Failed to inspect volume {}: {}", name, response.error_message()).into());
+    }
+
+    DockerVolume::from_json(&serde_json::from_slice(&response.body)?)
+}
+
+/// Waits until a container is healthy.
+///
+/// A container with a healthcheck is healthy once Docker reports it as `healthy`. A container without
+/// a healthcheck is healthy once it has been running for `health_min_running` seconds.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `container_id` - The ID of the container.
+/// * `timeout` - How long to wait for the container to become healthy.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - An empty result if the container is healthy, or an error naming the container
+///   if it does not become healthy within the timeout.
+pub fn wait_until_healthy(docker_config: &DockerConfig, container_id: &str, timeout: Duration) -> Result<(), Box<dyn Error>> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let container = inspect_container(docker_config, container_id)?;
+        let name = container["Name"].as_str().unwrap_or(container_id).trim_start_matches('/').to_string();
+        let state = &container["State"];
+        let status = state["Status"].as_str().unwrap_or("unknown");
+
+        let (healthy, reported_status) = match state["Health"]["Status"].as_str() {
+            Some(health) => (health == "healthy", health.to_string()),
+            None => {
+                let running_for = state["StartedAt"].as_str()
+                    .and_then(|started_at| DateTime::parse_from_rfc3339(started_at).ok())
+                    .map(|started_at| Utc::now().signed_duration_since(started_at).num_seconds())
+                    .unwrap_or(0);
+                (status == "running" && running_for >= docker_config.health_min_running as i64, status.to_string())
+            }
+        };
+
+        if healthy { return Ok(()); }
+        if Instant::now() >= deadline {
+            return Err(format!("Container {} did not become healthy within {} seconds, it is {}.",
+                               name, timeout.as_secs(), reported_status).into());
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// Retrieves the consistency mode a container requests through its labels, which overrides the configured one.
+///
+/// The `backup.mode` label sets the mode (`stop`, `pause` or `none`), and `backup.stop=false` keeps
+/// the container running unless `backup.mode` is set as well.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `container_id` - The ID of the container.
+///
+/// # Returns
+///
+/// * `Result<Option<ConsistencyMode>, Box<dyn Error>>` - The requested mode, `None` if the container has no such
+///   labels, or an error if a label is invalid or something goes wrong.
+pub fn get_container_consistency_mode(docker_config: &DockerConfig, container_id: &str) -> Result<Option<ConsistencyMode>, Box<dyn Error>> {
+    let container = inspect_container(docker_config, container_id)?;
+    let labels = &container["Config"]["Labels"];
+    let name = container["Name"].as_str().unwrap_or(container_id).trim_start_matches('/');
+
+    if let Some(mode) = labels["backup.mode"].as_str() {
+        let mode = ConsistencyMode::from_name(mode)
+            .map_err(|e| format!("Invalid label backup.mode of container {}: {}", name, e))?;
+        return Ok(Some(mode));
+    }
+
+    match labels["backup.stop"].as_str().map(|stop| stop.trim().to_lowercase()) {
+        Some(stop) if stop == "false" => Ok(Some(ConsistencyMode::None)),
+        Some(stop) if stop == "true" => Ok(None),
+        Some(stop) => Err(format!("Invalid label backup.stop '{}' of container {}, expected true or false.", stop, name).into()),
+        None => Ok(None),
+    }
+}
+
+/// Retrieves the labels of a container.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `container_id` - The ID of the container.
+///
+/// # Returns
+///
+/// * `Result<HashMap<String, String>, Box<dyn Error>>` - The labels of the container, or an error if something goes wrong.
+pub fn get_container_labels(docker_config: &DockerConfig, container_id: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let container = inspect_container(docker_config, container_id)?;
+    Ok(container["Config"]["Labels"].as_object().into_iter().flatten()
+        .filter_map(|(key, value)| value.as_str().map(|value| (key.clone(), value.to_string())))
+        .collect())
+}
+
+/// Runs a shell command inside a running container, like `docker exec <container> sh -c <command>`.
+///
+/// The Docker Engine API cannot kill an exec instance, so a command that does not finish in time is abandoned
+/// and keeps running inside the container.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `container_id` - The ID of the container.
+/// * `command` - The command, which is run with `sh -c`.
+/// * `timeout` - How long to wait for the command to finish.
+///
+/// # Returns
+///
+/// * `Result<String, Box<dyn Error>>` - The output of the command, or an error with the output if the command fails,
+///   does not finish in time or cannot be run.
+pub fn exec_in_container(docker_config: &DockerConfig,
+                         container_id: &str,
+                         command: &str,
+                         timeout: Duration) -> Result<String, Box<dyn Error>> {
+    let exec = serde_json::json!({ "AttachStdout": true, "AttachStderr": true, "Cmd": ["sh", "-c", command] });
+    let response = request_with_body(docker_config, "POST", &format!("/containers/{}/exec", container_id),
+                                     Some(&exec), docker_config.timeout())?;
+    if response.status != 201 {
+        return Err(format!("Failed to run '{}' in container {}: {}", command, container_id, response.error_message()).into());
+    }
+    let exec_id = serde_json::from_slice::<Value>(&response.body)?["Id"].as_str()
+        .ok_or("The Docker daemon did not return the ID of the exec instance.")?.to_string();
+
+    // The response streams the output until the command exits
+    let start = serde_json::json!({ "Detach": false, "Tty": false });
+    let response = request_with_body(docker_config, "POST", &format!("/exec/{}/start", exec_id), Some(&start), timeout)
+        .map_err(|e| format!("Command '{}' in container {} did not finish: {}", command, container_id, e))?;
+    if response.status != 200 {
+        return Err(format!("Failed to run '{}' in container {}: {}", command, container_id, response.error_message()).into());
+    }
+    let output = demultiplex_output(&response.body);
+
+    let response = request(docker_config, "GET", &format!("/exec/{}/json", exec_id))?;
+    let exit_code = serde_json::from_slice::<Value>(&response.body)?["ExitCode"].as_i64().unwrap_or(-1);
+    if exit_code != 0 {
+        return Err(format!("Command '{}' in container {} failed with exit code {}: {}",
+                           command, container_id, exit_code, output.trim()).into());
+    }
+    Ok(output)
+}
+
+/// Maps every running container using any of the given volumes to the volumes it uses.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `volumes` - The names of the volumes.
+///
+/// # Returns
+///
+/// * `Result<BTreeMap<String, Vec<String>>, Box<dyn Error>>` - The volumes used by each container ID, or an error if something goes wrong.
+pub fn map_containers_to_volumes(docker_config: &DockerConfig, volumes: &[String]) -> Result<BTreeMap<String, Vec<String>>, Box<dyn Error>> {
+    let mut container_volumes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for volume in volumes {
+        for container_id in get_container_ids(docker_config, volume)? {
+            container_volumes.entry(container_id).or_default().push(volume.clone());
+        }
+    }
+
+    Ok(container_volumes)
 }
 
 /// Retrieves the ID of the Docker container running this function.
 ///
-/// This function uses the `hostname` command to get the ID of the Docker container
-/// in which the function is being executed. The container ID is returned as a string.
+/// Docker sets the host name of a container to the beginning of its ID, so the ID is read
+/// from `/etc/hostname`. A host name that is not 12 to 64 lowercase hex characters was set by the
+/// user (e.g. with `hostname:` in Compose) and is not an ID.
+///
+/// # Returns
+///
+/// * `Result<Option<String>, Box<dyn Error>>` - The short or full container ID, `None` if the host name is not an ID,
+///   or an error if `/etc/hostname` cannot be read.
+fn get_my_container_id() -> Result<Option<String>, Box<dyn Error>> {
+    let host_name = fs::read_to_string("/etc/hostname")?;
+    Ok(container_id_from_host_name(host_name.trim()))
+}
+
+/// Returns the host name if it is a short or full container ID, i.e. 12 to 64 lowercase hex characters.
+fn container_id_from_host_name(host_name: &str) -> Option<String> {
+    let is_id = (12..=64).contains(&host_name.len())
+        && host_name.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+    Some(host_name.to_string()).filter(|_| is_id)
+}
+
+/// Returns whether a full container ID belongs to the container with the given short (12 characters) or full ID.
+fn is_same_container(container_id: &str, short_or_full_id: &str) -> bool {
+    container_id == short_or_full_id || (short_or_full_id.len() == 12 && container_id.get(..12) == Some(short_or_full_id))
+}
+
+/// Retrieves the IDs of all running containers matching the given filters.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `filters` - The filters as expected by the Docker Engine API, e.g. `{"volume": ["my_volume"]}`.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>, Box<dyn Error>>` - The IDs of the containers, or an error if something goes wrong.
+fn list_container_ids(docker_config: &DockerConfig, filters: Value) -> Result<Vec<String>, Box<dyn Error>> {
+    let response = request(docker_config, "GET", &format!("/containers/json?filters={}", uri_encode(&filters.to_string())))?;
+    if response.status != 200 {
+        return Err(response.error_message().into());
+    }
+
+    let containers: Value = serde_json::from_slice(&response.body)?;
+    Ok(containers.as_array().ok_or("The Docker daemon returned an invalid container list.")?
+        .iter()
+        .filter_map(|container| container["Id"].as_str())
+        .map(|container_id| container_id.to_string())
+        .collect())
+}
+
+/// Retrieves the low-level information of a container, as shown by `docker inspect`.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `container` - The ID or name of the container.
+///
+/// # Returns
+///
+/// * `Result<Value, Box<dyn Error>>` - The information of the container, or an error if something goes wrong.
+fn inspect_container(docker_config: &DockerConfig, container: &str) -> Result<Value, Box<dyn Error>> {
+    let response = request(docker_config, "GET", &format!("/containers/{}/json", uri_encode(container)))?;
+    if response.status != 200 {
+        return Err(format!("Failed to inspect container {}: {}", container, response.error_message()).into());
+    }
+
+    Ok(serde_json::from_slice(&response.body)?)
+}
+
+/// A response of the Docker Engine API.
+struct Response {
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// Returns the error message sent by the Docker daemon, or the status code if there is none.
+    fn error_message(&self) -> String {
+        let message = serde_json::from_slice::<Value>(&self.body).ok()
+            .and_then(|body| body["message"].as_str().map(|message| message.to_string()));
+
+        match message {
+            Some(message) => format!("{} (HTTP {})", message, self.status),
+            None => format!("HTTP {}", self.status),
+        }
+    }
+}
+
+/// Sends an action like `stop` or `pause` for a container to the Docker daemon.
+///
+/// A container that is already in the requested state is not treated as an error.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `container_id` - The ID of the container.
+/// * `action` - The action, i.e. `start`, `stop`, `pause` or `unpause`.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - An empty result if successful, or an error with the reason the Docker daemon gave.
+fn post_container_action(docker_config: &DockerConfig, container_id: &str, action: &str) -> Result<(), Box<dyn Error>> {
+    let response = request(docker_config, "POST", &format!("/containers/{}/{}", container_id, action))?;
+
+    match response.status {
+        204 | 304 => Ok(()),
+        _ => Err(format!("Failed to {} container {}: {}", action, container_id, response.error_message()).into()),
+    }
+}
+
+/// Sends an HTTP request without a body to the Docker Engine API over its Unix socket.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `method` - The HTTP method, e.g. `GET`.
+/// * `path` - The path of the endpoint including the query, e.g. `/containers/json`.
+///
+/// # Returns
+///
+/// * `Result<Response, Box<dyn Error>>` - The response, or an error if the Docker daemon cannot be reached or does not answer in time.
+fn request(docker_config: &DockerConfig, method: &str, path: &str) -> Result<Response, Box<dyn Error>> {
+    request_with_body(docker_config, method, path, None, docker_config.timeout())
+}
+
+/// Sends an HTTP request to the Docker Engine API over its Unix socket.
+///
+/// # Arguments
+///
+/// * `docker_config` - The connection to the Docker daemon.
+/// * `method` - The HTTP method, e.g. `POST`.
+/// * `path` - The path of the endpoint including the query, e.g. `/containers/json`.
+/// * `body` - The JSON body of the request, if any.
+/// * `timeout` - How long to wait for the whole response of the Docker daemon.
 ///
 /// # Returns
 ///
-/// * `Result<String, Box<dyn Error>>` - The container ID as a string, or an error if something goes wrong.
-fn get_my_container_id() -> Result<String, Box<dyn Error>> {
-    let output = Command::new("hostname").output()?;
-    let container_id = String::from_utf8(output.stdout)?.trim().to_string();
-    Ok(container_id)
+/// * `Result<Response, Box<dyn Error>>` - The response, or an error if the Docker daemon cannot be reached or does not answer in time.
+fn request_with_body(docker_config: &DockerConfig,
+                     method: &str,
+                     path: &str,
+                     body: Option<&Value>,
+                     timeout: Duration) -> Result<Response, Box<dyn Error>> {
+    let mut stream = UnixStream::connect(&docker_config.socket_path)
+        .map_err(|e| format!("Failed to connect to the Docker daemon at {}: {}", docker_config.socket_path, e))?;
+    stream.set_write_timeout(Some(docker_config.timeout()))?;
+
+    let body = body.map(|body| body.to_string()).unwrap_or_default();
+    let content_type = if body.is_empty() { "" } else { "Content-Type: application/json\r\n" };
+    write!(stream, "{} {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+           method, path, content_type, body.len(), body)?;
+
+    // The read timeout only limits a single read, so the deadline is checked across all of them
+    let deadline = Instant::now() + timeout;
+    let timed_out = || format!("The Docker daemon did not answer {} {} within {} seconds.", method, path, timeout.as_secs());
+    let mut raw_response = Vec::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() { return Err(timed_out().into()); }
+        stream.set_read_timeout(Some(remaining))?;
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => raw_response.extend_from_slice(&buffer[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Err(timed_out().into()),
+            Err(e) => return Err(format!("Failed to read the response of the Docker daemon: {}", e).into()),
+        }
+    }
+
+    parse_response(&raw_response)
+}
+
+/// Parses a raw HTTP response, decoding a chunked body if necessary.
+fn parse_response(raw_response: &[u8]) -> Result<Response, Box<dyn Error>> {
+    const INVALID_RESPONSE: &str = "The Docker daemon sent an invalid response.";
+
+    let header_end = find(raw_response, b"\r\n\r\n").ok_or(INVALID_RESPONSE)?;
+    let header = std::str::from_utf8(&raw_response[..header_end])?;
+    let mut header_lines = header.split("\r\n");
+
+    let status = header_lines.next()
+        .and_then(|status_line| status_line.split(' ').nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(INVALID_RESPONSE)?;
+    let chunked = header_lines.any(|line| {
+        let line = line.to_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+
+    let mut body = &raw_response[header_end + 4..];
+    let mut decoded_body = Vec::new();
+    if chunked {
+        loop {
+            let size_end = find(body, b"\r\n").ok_or(INVALID_RESPONSE)?;
+            let size = std::str::from_utf8(&body[..size_end])?.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| INVALID_RESPONSE)?;
+            if size == 0 { break; }
+
+            let chunk = body.get(size_end + 2..size_end + 2 + size).ok_or(INVALID_RESPONSE)?;
+            decoded_body.extend_from_slice(chunk);
+            body = body.get(size_end + 4 + size..).ok_or(INVALID_RESPONSE)?;
+        }
+    } else {
+        decoded_body.extend_from_slice(body);
+    }
+
+    Ok(Response { status, body: decoded_body })
+}
+
+/// Joins the frames of a multiplexed stdout/stderr stream of the Docker Engine API into a single text.
+///
+/// Every frame starts with an 8 byte header holding the stream type and the big-endian length of the frame.
+fn demultiplex_output(stream: &[u8]) -> String {
+    let mut output = Vec::new();
+    let mut rest = stream;
+
+    while rest.len() >= 8 {
+        let length = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let Some(frame) = rest.get(8..8 + length) else { break };
+        output.extend_from_slice(frame);
+        rest = &rest[8 + length..];
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// Returns the position of the first occurrence of `pattern` in `data`.
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|window| window == pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_response_reads_a_plain_body() {
+        let response = parse_response(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"Id\":\"abc\"}").unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"{\"Id\":\"abc\"}");
+    }
+
+    #[test]
+    fn parse_response_decodes_a_chunked_body() {
+        let raw = b"HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\nC\r\n, container!\r\n0\r\n\r\n";
+        let response = parse_response(raw).unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body, b"hello, container!");
+    }
+
+    #[test]
+    fn parse_response_ignores_chunk_extensions() {
+        let raw = b"HTTP/1.1 200 OK\r\ntransfer-encoding: Chunked\r\n\r\n3;name=value\r\nabc\r\n0;last\r\n\r\n";
+        assert_eq!(parse_response(raw).unwrap().body, b"abc");
+    }
+
+    #[test]
+    fn parse_response_rejects_truncated_input() {
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nA\r\nshort").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn container_id_from_host_name_ignores_custom_host_names() {
+        assert_eq!(container_id_from_host_name("4f66ad9a0b2e").as_deref(), Some("4f66ad9a0b2e"));
+        assert!(container_id_from_host_name(&"a".repeat(64)).is_some());
+
+        for host_name in ["b", "", "deadbeef", "backup", "4F66AD9A0B2E", "4f66ad9a0b2g", &"a".repeat(65)] {
+            assert_eq!(container_id_from_host_name(host_name), None, "{}", host_name);
+        }
+    }
+
+    #[test]
+    fn is_same_container_compares_short_and_full_ids_exactly() {
+        let container_id = "4f66ad9a0b2e6c1b2d2c3b9d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f9012";
+        assert!(is_same_container(container_id, container_id));
+        assert!(is_same_container(container_id, "4f66ad9a0b2e"));
+
+        assert!(!is_same_container(container_id, "4f66ad9a0b2"));
+        assert!(!is_same_container(container_id, "4f66ad9a0b2e6"));
+        assert!(!is_same_container(container_id, "4f66ad9a0b2f"));
+        assert!(!is_same_container("4f66ad9a0b2", "4f66ad9a0b2e"));
+    }
+
+    #[test]
+    fn demultiplex_output_joins_stdout_and_stderr_frames() {
+        let mut stream = vec![1, 0, 0, 0, 0, 0, 0, 6];
+        stream.extend_from_slice(b"saved\n");
+        stream.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 8]);
+        stream.extend_from_slice(b"warning\n");
+        stream.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(demultiplex_output(&stream), "saved\nwarning\n");
+    }
+
+    #[test]
+    fn demultiplex_output_drops_a_truncated_frame() {
+        let mut stream = vec![1, 0, 0, 0, 0, 0, 0, 2];
+        stream.extend_from_slice(b"ok");
+        stream.extend_from_slice(&[1, 0, 0, 0, 0, 0, 1, 0]);
+        stream.extend_from_slice(b"cut off");
+        assert_eq!(demultiplex_output(&stream), "ok");
+        assert_eq!(demultiplex_output(&[1, 0, 0]), "");
+    }
 }
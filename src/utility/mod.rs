@@ -1,4 +1,6 @@
 pub mod docker;
+pub mod container_guard;
 pub mod compression;
-pub mod server;
+pub mod storage;
 pub mod configs;
+pub mod uri;
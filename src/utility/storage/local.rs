@@ -0,0 +1,89 @@
+use crate::utility::configs::local_config::LocalConfig;
+use crate::utility::storage::progress::{copy_with_progress, TransferProgress};
+use crate::utility::storage::{partial_file_name, FileStat, StorageBackend};
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::path::PathBuf;
+
+/// A storage backend that keeps backups in a local directory, such as a mounted NAS share.
+pub struct LocalStorage {
+    config: LocalConfig,
+}
+
+impl LocalStorage {
+    /// Creates a new `LocalStorage` instance with the provided `LocalConfig`.
+    pub fn new(config: LocalConfig) -> Self {
+        Self { config }
+    }
+
+    /// Builds the full path of a file inside the backup directory.
+    fn local_path(&self, file_name: &str) -> PathBuf {
+        PathBuf::from(&self.config.directory).join(file_name)
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    /// Copies a local file into the backup directory, creating the directory if it doesn't exist.
+    ///
+    /// The file is written under a temporary `.partial` name, synced to disk and renamed to its
+    /// final name once its size matches the local file.
+    fn upload_file(&self, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.config.directory)?;
+
+        let mut local_file = File::open(local_file_path)?;
+        let file_size = local_file.metadata()?.len();
+        let mut progress = TransferProgress::new(&format!("Uploading {}", file_name), file_size);
+
+        let partial_path = self.local_path(&partial_file_name(file_name));
+        let mut backup_file = File::create(&partial_path)?;
+        let copied = copy_with_progress(&mut local_file, &mut backup_file, &mut progress)?;
+        backup_file.sync_all()?;
+
+        if copied != file_size {
+            return Err(format!("Upload of {} is incomplete: expected {} bytes, copied {} bytes.",
+                               file_name, file_size, copied).into());
+        }
+
+        fs::rename(&partial_path, self.local_path(file_name))?;
+
+        Ok(())
+    }
+
+    /// Copies a file from the backup directory to a local path.
+    fn download_file(&self, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>> {
+        let mut backup_file = File::open(self.local_path(file_name))?;
+        let mut progress = TransferProgress::new(&format!("Downloading {}", file_name), backup_file.metadata()?.len());
+        copy_with_progress(&mut backup_file, &mut File::create(local_file_path)?, &mut progress)?;
+
+        Ok(())
+    }
+
+    /// Lists file names in the backup directory.
+    fn list_files(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(fs::read_dir(&self.config.directory)?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect())
+    }
+
+    /// Deletes a file from the backup directory.
+    fn delete_file(&self, file_name: &str) -> Result<(), Box<dyn Error>> {
+        fs::remove_file(self.local_path(file_name))
+            .map_err(|e| format!("Failed to delete file: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Retrieves the size of a file in the backup directory.
+    fn stat_file(&self, file_name: &str) -> Result<FileStat, Box<dyn Error>> {
+        let size = fs::metadata(self.local_path(file_name))?.len();
+
+        Ok(FileStat { size })
+    }
+
+    fn location(&self, file_name: &str) -> String {
+        self.local_path(file_name).display().to_string()
+    }
+}
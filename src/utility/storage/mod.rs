@@ -0,0 +1,105 @@
+pub mod server;
+pub mod local;
+pub mod s3;
+pub mod progress;
+pub mod resume;
+pub mod retry;
+
+use crate::backup::parse_backup_date;
+use std::error::Error;
+
+/// The suffix appended to the name of a file while it is being uploaded.
+pub const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Returns the temporary name under which a file is uploaded before it is renamed to its final name.
+pub fn partial_file_name(file_name: &str) -> String {
+    format!("{}{}", file_name, PARTIAL_SUFFIX)
+}
+
+/// Metadata of a file stored in a backup destination.
+///
+/// - `size`: The size of the file in bytes.
+pub struct FileStat {
+    pub size: u64,
+}
+
+/// A destination where backup archives are stored.
+///
+/// All file names passed to and returned from a `StorageBackend` are relative to the
+/// directory (or prefix) configured for the destination, e.g. `backup-2024-09-10T16-02-47.tar.gz`.
+pub trait StorageBackend {
+    /// Uploads a local file to the destination under the given file name.
+    ///
+    /// The upload must be atomic: the file only appears under `file_name` once the transfer
+    /// is complete and verified, so an interrupted upload never leaves a truncated backup behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - The name under which the file will be stored in the destination.
+    /// * `local_file_path` - The path of the local file to be uploaded.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if the upload is successful, or an error if something goes wrong.
+    fn upload_file(&self, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Downloads a file from the destination to a local path.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - The name of the file in the destination.
+    /// * `local_file_path` - The path where the downloaded file will be saved locally.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if the download is successful, or an error if something goes wrong.
+    fn download_file(&self, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Lists the names of all files stored in the destination.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<String>, Box<dyn Error>>` - A vector of file names if successful, or an error if something goes wrong.
+    fn list_files(&self) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// Deletes a file from the destination.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - The name of the file to be deleted.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if the deletion is successful, or an error if something goes wrong.
+    fn delete_file(&self, file_name: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Retrieves the metadata of a file stored in the destination.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - The name of the file.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<FileStat, Box<dyn Error>>` - The metadata of the file, or an error if it does not exist or something goes wrong.
+    fn stat_file(&self, file_name: &str) -> Result<FileStat, Box<dyn Error>>;
+
+    /// Returns a human-readable location of a file in the destination, used for logging.
+    fn location(&self, file_name: &str) -> String;
+
+    /// Retrieves the name of the latest backup file from the destination.
+    ///
+    /// The latest backup is determined by the timestamp encoded in the backup file name.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, Box<dyn Error>>` - The name of the latest backup file, or an error if no backups are found or something goes wrong.
+    fn get_latest_backup_file_name(&self) -> Result<String, Box<dyn Error>> {
+        self.list_files()?
+            .into_iter()
+            .filter_map(|file_name| parse_backup_date(&file_name).map(|date| (file_name, date)))
+            .max_by_key(|(_, date)| *date)
+            .map(|(file_name, _)| file_name)
+            .ok_or_else(|| "No backup files found in the destination.".into())
+    }
+}
@@ -0,0 +1,77 @@
+use std::io;
+use std::io::{Read, Write};
+
+/// The size of the chunks in which files are transferred.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The percentage steps at which the progress of a transfer is reported.
+const REPORT_STEP_PERCENT: u64 = 10;
+
+/// Tracks and reports the progress of a file transfer.
+pub struct TransferProgress {
+    description: String,
+    total_size: u64,
+    transferred: u64,
+    reported_percent: u64,
+}
+
+impl TransferProgress {
+    /// Creates a new `TransferProgress` for a transfer of `total_size` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `description` - A description of the transfer used in the progress messages, e.g. `Uploading backup.tar.gz`.
+    /// * `total_size` - The total number of bytes to be transferred.
+    pub fn new(description: &str, total_size: u64) -> Self {
+        Self { description: description.to_string(), total_size, transferred: 0, reported_percent: 0 }
+    }
+
+    /// Records that `bytes` more bytes were transferred and prints the progress whenever
+    /// another `REPORT_STEP_PERCENT` percent of the transfer is completed.
+    pub fn advance(&mut self, bytes: u64) {
+        self.transferred += bytes;
+
+        let percent = (self.transferred * 100).checked_div(self.total_size).unwrap_or(100).min(100);
+        if percent >= self.reported_percent + REPORT_STEP_PERCENT {
+            self.reported_percent = percent - percent % REPORT_STEP_PERCENT;
+            println!("{}: {}% ({} of {} MiB)", self.description, self.reported_percent,
+                     self.transferred / (1024 * 1024), self.total_size / (1024 * 1024));
+        }
+    }
+}
+
+/// Copies all data from `reader` to `writer` in chunks of `CHUNK_SIZE` bytes, reporting the progress.
+///
+/// Only a single chunk is held in memory at a time, so files of any size can be transferred.
+///
+/// # Arguments
+///
+/// * `reader` - The source of the data.
+/// * `writer` - The destination of the data.
+/// * `progress` - The progress tracker of the transfer.
+///
+/// # Returns
+///
+/// * `io::Result<u64>` - The number of bytes copied, or an I/O error if something goes wrong.
+pub fn copy_with_progress(reader: &mut impl Read,
+                          writer: &mut impl Write,
+                          progress: &mut TransferProgress) -> io::Result<u64> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut copied = 0;
+
+    loop {
+        let length = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(length) => length,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        writer.write_all(&buffer[..length])?;
+        copied += length as u64;
+        progress.advance(length as u64);
+    }
+
+    writer.flush()?;
+    Ok(copied)
+}
@@ -0,0 +1,84 @@
+use sha2::{Digest, Sha256};
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+/// The number of bytes before the resume offset that are compared between source and target.
+const VERIFY_SIZE: u64 = 1024 * 1024;
+
+/// Determines the offset at which an interrupted transfer from `source` to `target` can be resumed.
+///
+/// The existing target is only trusted if it's not larger than the source and the SHA-256 checksum
+/// of its last `VERIFY_SIZE` bytes matches the same range of the source. Otherwise the transfer
+/// has to start from the beginning and `0` is returned.
+///
+/// Only that last range is compared, so reading a multi-GB target again stays cheap. Corruption in
+/// the earlier bytes of the target goes undetected.
+///
+/// # Arguments
+///
+/// * `source` - The file being transferred.
+/// * `source_size` - The size of the source in bytes.
+/// * `target` - The partially transferred file.
+/// * `target_size` - The size of the target in bytes.
+///
+/// # Returns
+///
+/// * `io::Result<u64>` - The offset to resume at, or an I/O error if something goes wrong.
+pub fn resume_offset(source: &mut (impl Read + Seek),
+                     source_size: u64,
+                     target: &mut (impl Read + Seek),
+                     target_size: u64) -> io::Result<u64> {
+    if target_size == 0 || target_size > source_size { return Ok(0); }
+
+    let start = target_size.saturating_sub(VERIFY_SIZE);
+    if checksum_range(source, start, target_size)? != checksum_range(target, start, target_size)? {
+        return Ok(0);
+    }
+
+    Ok(target_size)
+}
+
+/// Computes the SHA-256 checksum of the bytes between `start` and `end` of a file.
+fn checksum_range(file: &mut (impl Read + Seek), start: u64, end: u64) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut hasher = Sha256::new();
+    io::copy(&mut file.take(end - start), &mut hasher)?;
+
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn offset(source: &[u8], target: &[u8]) -> u64 {
+        resume_offset(&mut Cursor::new(source), source.len() as u64, &mut Cursor::new(target), target.len() as u64).unwrap()
+    }
+
+    #[test]
+    fn resume_offset_continues_a_matching_prefix() {
+        assert_eq!(offset(b"0123456789", b"01234"), 5);
+        assert_eq!(offset(b"0123456789", b"0123456789"), 10);
+    }
+
+    #[test]
+    fn resume_offset_restarts_an_empty_larger_or_different_target() {
+        assert_eq!(offset(b"0123456789", b""), 0);
+        assert_eq!(offset(b"01234", b"0123456789"), 0);
+        assert_eq!(offset(b"0123456789", b"01x34"), 0);
+    }
+
+    #[test]
+    fn resume_offset_only_compares_the_last_bytes() {
+        let source = vec![7u8; 3 * VERIFY_SIZE as usize];
+        let mut target = source[..2 * VERIFY_SIZE as usize].to_vec();
+
+        target[0] = 0;
+        assert_eq!(offset(&source, &target), 2 * VERIFY_SIZE);
+
+        *target.last_mut().unwrap() = 0;
+        assert_eq!(offset(&source, &target), 0);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::utility::configs::retry_policy::RetryPolicy;
+use crate::utility::storage::{FileStat, StorageBackend};
+use std::error::Error;
+use std::fmt;
+use std::thread::sleep;
+
+/// An error that retrying the operation cannot fix, e.g. a rejected host key or failed authentication.
+///
+/// `RetryingStorage` gives up on the first attempt failing with a `PermanentError`.
+#[derive(Debug)]
+pub struct PermanentError(pub String);
+
+impl fmt::Display for PermanentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for PermanentError {}
+
+/// A storage backend that retries every failed operation of another backend with exponential backoff.
+pub struct RetryingStorage {
+    inner: Box<dyn StorageBackend>,
+    policy: RetryPolicy,
+}
+
+impl RetryingStorage {
+    /// Creates a new `RetryingStorage` wrapping `inner` with the provided `RetryPolicy`.
+    pub fn new(inner: Box<dyn StorageBackend>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Runs an operation, retrying it according to the retry policy until it succeeds.
+    ///
+    /// Operations failing with a `PermanentError` are not retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `description` - A description of the operation used in the log messages, e.g. `upload of backup.tar.gz`.
+    /// * `operation` - The operation to be run.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<T, Box<dyn Error>>` - The result of the first successful attempt, or the error of the last attempt.
+    fn retry<T>(&self,
+                description: &str,
+                mut operation: impl FnMut() -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        let attempts = self.policy.count + 1;
+        let mut attempt = 1;
+
+        loop {
+            match operation() {
+                Ok(result) => return Ok(result),
+                Err(e) if e.is::<PermanentError>() => {
+                    println!("Attempt {}/{} of the {} failed: {}. Retrying cannot fix this, giving up.",
+                             attempt, attempts, description, e);
+                    return Err(e);
+                }
+                Err(e) if attempt < attempts => {
+                    let backoff = self.policy.backoff_for(attempt);
+                    println!("Attempt {}/{} of the {} failed: {}. Retrying in {} seconds.",
+                             attempt, attempts, description, e, backoff.as_secs());
+                    sleep(backoff);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    println!("Attempt {}/{} of the {} failed: {}. Giving up.", attempt, attempts, description, e);
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+impl StorageBackend for RetryingStorage {
+    fn upload_file(&self, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>> {
+        self.retry(&format!("upload of {}", file_name), || self.inner.upload_file(file_name, local_file_path))
+    }
+
+    fn download_file(&self, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>> {
+        self.retry(&format!("download of {}", file_name), || self.inner.download_file(file_name, local_file_path))
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.retry("file listing", || self.inner.list_files())
+    }
+
+    fn delete_file(&self, file_name: &str) -> Result<(), Box<dyn Error>> {
+        self.retry(&format!("deletion of {}", file_name), || self.inner.delete_file(file_name))
+    }
+
+    fn stat_file(&self, file_name: &str) -> Result<FileStat, Box<dyn Error>> {
+        self.retry(&format!("stat of {}", file_name), || self.inner.stat_file(file_name))
+    }
+
+    fn location(&self, file_name: &str) -> String {
+        self.inner.location(file_name)
+    }
+}
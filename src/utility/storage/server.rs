@@ -0,0 +1,495 @@
+use crate::utility::configs::jump_host_config::JumpHostConfig;
+use crate::utility::configs::server_config::ServerConfig;
+use crate::utility::storage::progress::{copy_with_progress, TransferProgress};
+use crate::utility::storage::resume::resume_offset;
+use crate::utility::storage::retry::PermanentError;
+use crate::utility::storage::{partial_file_name, FileStat, StorageBackend};
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use ssh2::{Channel, CheckResult, HashType, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp};
+use std::error::Error;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// The interval in seconds at which the server is asked to keep an idle connection open.
+const KEEPALIVE_INTERVAL_IN_SECONDS: u32 = 30;
+
+/// A storage backend that keeps backups on a remote server accessed via SSH.
+///
+/// A single SSH connection is opened on first use and shared by all operations of the instance,
+/// so a backup run performs only one handshake. A connection that failed is replaced on the next operation.
+pub struct Server {
+    config: ServerConfig,
+    timeout: Duration,
+    connection: Mutex<Option<Connection>>,
+}
+
+/// An open SSH connection to the server with its SFTP subsystem.
+struct Connection {
+    session: Session,
+    sftp: Sftp,
+}
+
+/// A host on the way to the server, i.e. a jump host or the server itself, with the credentials
+/// and the host key pin used for it.
+struct Hop<'a> {
+    host: &'a str,
+    port: &'a str,
+    user: &'a str,
+    ssh_key_path: Option<&'a str>,
+    ssh_key: Option<&'a str>,
+    ssh_key_passphrase: Option<&'a str>,
+    password: Option<&'a str>,
+    host_key_fingerprint: Option<&'a str>,
+}
+
+impl<'a> Hop<'a> {
+    /// Describes the server itself as the last hop.
+    fn server(config: &'a ServerConfig) -> Self {
+        Self {
+            host: &config.server_ip,
+            port: &config.server_port,
+            user: &config.server_user,
+            ssh_key_path: config.ssh_key_path.as_deref(),
+            ssh_key: config.ssh_key.as_deref(),
+            ssh_key_passphrase: config.ssh_key_passphrase.as_deref(),
+            password: config.server_password.as_deref(),
+            host_key_fingerprint: config.host_key_fingerprint.as_deref(),
+        }
+    }
+
+    /// Describes a jump host. A jump host without its own private key uses the server's key.
+    fn jump_host(jump_host: &'a JumpHostConfig, config: &'a ServerConfig) -> Self {
+        let has_own_key = jump_host.ssh_key.is_some() || jump_host.ssh_key_path.is_some();
+        let key_config = if has_own_key {
+            (jump_host.ssh_key_path.as_deref(), jump_host.ssh_key.as_deref(), jump_host.ssh_key_passphrase.as_deref())
+        } else {
+            (config.ssh_key_path.as_deref(), config.ssh_key.as_deref(), config.ssh_key_passphrase.as_deref())
+        };
+
+        Self {
+            host: &jump_host.host,
+            port: &jump_host.port,
+            user: &jump_host.user,
+            ssh_key_path: key_config.0,
+            ssh_key: key_config.1,
+            ssh_key_passphrase: key_config.2,
+            password: jump_host.password.as_deref(),
+            host_key_fingerprint: jump_host.host_key_fingerprint.as_deref(),
+        }
+    }
+}
+
+impl Server {
+    /// Creates a new `Server` instance with the provided `ServerConfig`.
+    ///
+    /// The `timeout` limits connecting to the server as well as every blocking SSH operation.
+    pub fn new(config: ServerConfig, timeout: Duration) -> Self {
+        Self { config, timeout, connection: Mutex::new(None) }
+    }
+
+    /// Establishes an SSH connection to the server.
+    ///
+    /// If jump hosts are configured, the connection is tunneled through them: the first jump host is
+    /// connected to directly, and each further host is reached through a `direct-tcpip` channel opened
+    /// on the session of the previous one. The host key of every hop is verified before any credentials
+    /// are sent to it.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Session, Box<dyn Error>>` - A `Session` instance if successful, or an error if something goes wrong.
+    fn connect(&self) -> Result<Session, Box<dyn Error>> {
+        let hops: Vec<Hop> = self.config.jump_hosts.iter()
+            .map(|jump_host| Hop::jump_host(jump_host, &self.config))
+            .chain(std::iter::once(Hop::server(&self.config)))
+            .collect();
+
+        let first_hop = &hops[0];
+        let address = (first_hop.host, first_hop.port.parse::<u16>()?)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| format!("Failed to resolve the address of {}.", first_hop.host))?;
+        let tcp = TcpStream::connect_timeout(&address, self.timeout)?;
+        let mut sess = self.open_session(tcp, first_hop)?;
+
+        for (previous_hop, hop) in hops.iter().zip(hops.iter().skip(1)) {
+            let channel = sess.channel_direct_tcpip(hop.host, hop.port.parse()?, None)
+                .map_err(|e| format!("Failed to open a tunnel through {} to {}: {}", previous_hop.host, hop.host, e))?;
+            let (local_stream, tunnel_stream) = UnixStream::pair()?;
+            forward_channel(sess, channel, tunnel_stream)?;
+            sess = self.open_session(local_stream, hop)?;
+        }
+
+        Ok(sess)
+    }
+
+    /// Starts an SSH session on a stream to a hop, verifies its host key and authenticates.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The TCP stream to the hop, or the local end of a tunnel to it.
+    /// * `hop` - The hop the stream leads to.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Session, Box<dyn Error>>` - The authenticated session, or an error naming the hop if something goes wrong.
+    fn open_session(&self, stream: impl AsRawFd + 'static, hop: &Hop) -> Result<Session, Box<dyn Error>> {
+        let open = || -> Result<Session, Box<dyn Error>> {
+            let mut sess = Session::new()?;
+            sess.set_timeout(self.timeout.as_millis() as u32);
+            sess.set_keepalive(true, KEEPALIVE_INTERVAL_IN_SECONDS);
+            sess.set_tcp_stream(stream);
+            sess.handshake()?;
+            self.verify_host_key(&sess, hop)?;
+            self.authenticate(&sess, hop)?;
+            Ok(sess)
+        };
+
+        if self.config.jump_hosts.is_empty() { return open(); }
+        open().map_err(|e| {
+            let message = format!("Connection to {} failed: {}", hop.host, e);
+            if e.is::<PermanentError>() { PermanentError(message).into() } else { message.into() }
+        })
+    }
+
+    /// Authenticates the session, trying the ssh-agent, the private key and the password in this order.
+    ///
+    /// # Arguments
+    ///
+    /// * `sess` - The session after the host key was verified.
+    /// * `hop` - The host the session is connected to.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if any method succeeds, or an error listing why each configured method failed.
+    fn authenticate(&self, sess: &Session, hop: &Hop) -> Result<(), Box<dyn Error>> {
+        let user = hop.user;
+        let mut failures = Vec::new();
+
+        if self.config.use_ssh_agent {
+            match sess.userauth_agent(user) {
+                Ok(()) if sess.authenticated() => return Ok(()),
+                Ok(()) => failures.push("ssh-agent: no identity was accepted".to_string()),
+                Err(e) => failures.push(format!("ssh-agent: {}", e)),
+            }
+        }
+
+        let private_key = match (hop.ssh_key, hop.ssh_key_path) {
+            (Some(key), _) => Some(key.to_string()),
+            (None, Some(path)) => Some(fs::read_to_string(path)?),
+            (None, None) => None,
+        };
+        if let Some(private_key) = private_key {
+            match sess.userauth_pubkey_memory(user, None, &private_key, hop.ssh_key_passphrase) {
+                Ok(()) if sess.authenticated() => return Ok(()),
+                Ok(()) => failures.push("private key: the key was not accepted".to_string()),
+                Err(e) => failures.push(format!("private key: {}", e)),
+            }
+        }
+
+        if let Some(password) = hop.password {
+            match sess.userauth_password(user, password) {
+                Ok(()) if sess.authenticated() => return Ok(()),
+                Ok(()) => failures.push("password: the password was not accepted".to_string()),
+                Err(e) => failures.push(format!("password: {}", e)),
+            }
+        }
+
+        if failures.is_empty() {
+            return Err(PermanentError("Authentication failed: no SSH key, password or ssh-agent is configured.".to_string()).into());
+        }
+
+        Err(PermanentError(format!("Authentication failed ({}).", failures.join("; "))).into())
+    }
+
+    /// Verifies the host key presented by a hop before any credentials are sent.
+    ///
+    /// If a host key fingerprint is pinned for the hop, the key must match it. Otherwise
+    /// the key must be listed in the `known_hosts` file. Unknown keys are only accepted, and added
+    /// to the `known_hosts` file, if trust on first use is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `sess` - The session after the SSH handshake.
+    /// * `hop` - The host the session is connected to.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - An empty result if the host key is trusted, or an error if it is not.
+    fn verify_host_key(&self, sess: &Session, hop: &Hop) -> Result<(), Box<dyn Error>> {
+        let (host_key, host_key_type) = sess.host_key().ok_or("The server did not present a host key.")?;
+
+        if let Some(expected_fingerprint) = hop.host_key_fingerprint {
+            let hash = sess.host_key_hash(HashType::Sha256).ok_or("Failed to compute the host key fingerprint.")?;
+            let fingerprint = format!("SHA256:{}", STANDARD_NO_PAD.encode(hash));
+
+            if fingerprint != expected_fingerprint.trim() {
+                return Err(PermanentError(format!("Host key verification failed: the server presented {}, expected {}.",
+                                                  fingerprint, expected_fingerprint)).into());
+            }
+            return Ok(());
+        }
+
+        let port: u16 = hop.port.parse()?;
+        let known_hosts_path = Path::new(&self.config.known_hosts_path);
+        let mut known_hosts = sess.known_hosts()?;
+        if known_hosts_path.exists() {
+            known_hosts.read_file(known_hosts_path, KnownHostFileKind::OpenSSH)?;
+        }
+
+        match known_hosts.check_port(hop.host, port, host_key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(PermanentError(format!(
+                "Host key verification failed: the host key of {} does not match the one in {}. \
+                 The server may have been reinstalled, or someone may be intercepting the connection.",
+                hop.host, self.config.known_hosts_path)).into()),
+            CheckResult::NotFound if self.config.trust_on_first_use => {
+                let host = if port == 22 {
+                    hop.host.to_string()
+                } else {
+                    format!("[{}]:{}", hop.host, port)
+                };
+                known_hosts.add(&host, host_key, "", host_key_type.into())?;
+                known_hosts.write_file(known_hosts_path, KnownHostFileKind::OpenSSH)?;
+                println!("Trusting the previously unknown host key of {} and adding it to {}",
+                         host, self.config.known_hosts_path);
+                Ok(())
+            }
+            CheckResult::NotFound => Err(PermanentError(format!(
+                "Host key verification failed: {} is not listed in {}. Mount a known_hosts file, \
+                 set SSH_HOST_KEY_FINGERPRINT or enable SSH_TRUST_ON_FIRST_USE.",
+                hop.host, self.config.known_hosts_path)).into()),
+            CheckResult::Failure => Err("Failed to check the host key against the known_hosts file.".into()),
+        }
+    }
+
+    /// Runs an operation on the SFTP subsystem of the shared connection to the server.
+    ///
+    /// The open connection is reused if the server still answers a keepalive, otherwise a new
+    /// connection is established. If the operation fails, the connection is dropped so that the
+    /// next operation starts with a fresh one.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation to be run.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<T, Box<dyn Error>>` - The result of the operation, or an error if connecting or the operation fails.
+    fn with_sftp<T>(&self, operation: impl FnOnce(&Sftp) -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+
+        let open_connection = match connection.take() {
+            Some(open_connection) if open_connection.session.keepalive_send().is_ok() => open_connection,
+            _ => {
+                let session = self.connect()?;
+                let sftp = session.sftp()?;
+                Connection { session, sftp }
+            }
+        };
+
+        let result = operation(&open_connection.sftp);
+        if result.is_ok() { *connection = Some(open_connection); }
+
+        result
+    }
+
+    /// Builds the full path of a file inside the server's backup directory.
+    fn remote_path(&self, file_name: &str) -> PathBuf {
+        Path::new(&self.config.server_directory).join(file_name)
+    }
+}
+
+impl StorageBackend for Server {
+    /// Uploads a local file to the remote server using SFTP.
+    ///
+    /// The file is written under a temporary `.partial` name and renamed to its final name
+    /// once the size on the server matches the local file. If a partial file from an interrupted
+    /// upload exists and matches the local file, the upload continues where it left off.
+    fn upload_file(&self, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>> {
+        self.with_sftp(|sftp| {
+            let mut local_file = File::open(local_file_path)?;
+            let file_size = local_file.metadata()?.len();
+            let mut progress = TransferProgress::new(&format!("Uploading {}", file_name), file_size);
+
+            let partial_path = self.remote_path(&partial_file_name(file_name));
+            let partial_size = sftp.stat(&partial_path).ok().and_then(|stat| stat.size).unwrap_or(0);
+            let mut remote_file = sftp.open_mode(&partial_path, OpenFlags::READ | OpenFlags::WRITE | OpenFlags::CREATE,
+                                                 0o644, OpenType::File)?;
+
+            let offset = resume_offset(&mut local_file, file_size, &mut remote_file, partial_size)?;
+            if offset == 0 {
+                remote_file = sftp.create(&partial_path)?;
+            } else {
+                println!("Resuming upload of {} at {} bytes", file_name, offset);
+                progress.advance(offset);
+            }
+
+            local_file.seek(SeekFrom::Start(offset))?;
+            remote_file.seek(SeekFrom::Start(offset))?;
+            copy_with_progress(&mut local_file, &mut remote_file, &mut progress)?;
+            drop(remote_file);
+
+            let remote_size = sftp.stat(&partial_path)?.size.unwrap_or(0);
+            if remote_size != file_size {
+                return Err(format!("Upload of {} is incomplete: expected {} bytes, found {} bytes.",
+                                   file_name, file_size, remote_size).into());
+            }
+
+            sftp.rename(&partial_path, &self.remote_path(file_name), None)?;
+
+            Ok(())
+        })
+    }
+
+    /// Downloads a file from the remote server using SFTP.
+    ///
+    /// If the local file already exists from an interrupted download and matches the file
+    /// on the server, the download continues where it left off.
+    fn download_file(&self, file_name: &str, local_file_path: &str) -> Result<(), Box<dyn Error>> {
+        self.with_sftp(|sftp| {
+            let mut remote_file = sftp.open(&self.remote_path(file_name))?;
+            let file_size = remote_file.stat()?.size.unwrap_or(0);
+            let mut progress = TransferProgress::new(&format!("Downloading {}", file_name), file_size);
+
+            let local_size = fs::metadata(local_file_path).map(|metadata| metadata.len()).unwrap_or(0);
+            let mut local_file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(local_file_path)?;
+
+            let offset = resume_offset(&mut remote_file, file_size, &mut local_file, local_size)?;
+            if offset > 0 {
+                println!("Resuming download of {} at {} bytes", file_name, offset);
+                progress.advance(offset);
+            }
+
+            local_file.set_len(offset)?;
+            local_file.seek(SeekFrom::Start(offset))?;
+            remote_file.seek(SeekFrom::Start(offset))?;
+            let copied = copy_with_progress(&mut remote_file, &mut local_file, &mut progress)?;
+
+            if offset + copied != file_size {
+                return Err(format!("Download of {} is incomplete: expected {} bytes, received {} bytes.",
+                                   file_name, file_size, offset + copied).into());
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Lists file names in the backup directory on the remote server.
+    fn list_files(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let entries = self.with_sftp(|sftp| Ok(sftp.readdir(Path::new(&self.config.server_directory))?))
+            .map_err(|e| format!("Failed to list files: {}", e))?;
+
+        Ok(entries.into_iter()
+            .filter(|(_, stat)| stat.is_file())
+            .filter_map(|(path, _)| path.file_name().and_then(|name| name.to_str()).map(|name| name.to_string()))
+            .collect())
+    }
+
+    /// Deletes a file from the backup directory on the remote server.
+    fn delete_file(&self, file_name: &str) -> Result<(), Box<dyn Error>> {
+        self.with_sftp(|sftp| Ok(sftp.unlink(&self.remote_path(file_name))?))
+            .map_err(|e| format!("Failed to delete file: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Retrieves the size of a file on the remote server.
+    fn stat_file(&self, file_name: &str) -> Result<FileStat, Box<dyn Error>> {
+        let stat = self.with_sftp(|sftp| Ok(sftp.stat(&self.remote_path(file_name))?))?;
+        let size = stat.size.ok_or("The server did not report the file size.")?;
+
+        Ok(FileStat { size })
+    }
+
+    fn location(&self, file_name: &str) -> String {
+        format!("{}@{}:{}", self.config.server_user, self.config.server_ip, self.remote_path(file_name).display())
+    }
+}
+
+/// Forwards data between a `direct-tcpip` channel of a jump host session and a local socket,
+/// so that the next SSH session can run on the other end of the socket.
+///
+/// The forwarding runs on its own thread, which owns the jump host session and ends, closing the
+/// session, once either side of the tunnel is closed.
+///
+/// # Arguments
+///
+/// * `session` - The session of the jump host the channel belongs to.
+/// * `channel` - The channel to the next hop.
+/// * `socket` - The local socket connected to the next session.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - An empty result if the forwarding thread was started, or an error if something goes wrong.
+fn forward_channel(session: Session, mut channel: Channel, mut socket: UnixStream) -> Result<(), Box<dyn Error>> {
+    const BUFFER_SIZE: usize = 64 * 1024;
+    const IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+    // Both sides are polled from a single thread, so neither may block
+    session.set_blocking(false);
+    socket.set_nonblocking(true)?;
+
+    thread::Builder::new().name("ssh-tunnel".to_string()).spawn(move || {
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut to_channel: Vec<u8> = Vec::new();
+        let mut to_socket: Vec<u8> = Vec::new();
+
+        let result: io::Result<()> = (|| loop {
+            let mut idle = true;
+
+            if to_channel.is_empty() {
+                match socket.read(&mut buffer) {
+                    Ok(0) => return Ok(()),
+                    Ok(length) => { to_channel.extend_from_slice(&buffer[..length]); idle = false; }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if !to_channel.is_empty() {
+                match channel.write(&to_channel) {
+                    Ok(length) => { to_channel.drain(..length); idle = false; }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if to_socket.is_empty() {
+                match channel.read(&mut buffer) {
+                    Ok(0) if channel.eof() => return Ok(()),
+                    Ok(0) => {}
+                    Ok(length) => { to_socket.extend_from_slice(&buffer[..length]); idle = false; }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if !to_socket.is_empty() {
+                match socket.write(&to_socket) {
+                    Ok(length) => { to_socket.drain(..length); idle = false; }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if idle {
+                // Keeps the jump host session alive while the tunnel is idle
+                let _ = session.keepalive_send();
+                thread::sleep(IDLE_SLEEP);
+            }
+        })();
+
+        if let Err(e) = result { println!("SSH tunnel closed: {}", e); }
+        let _ = channel.close();
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+/// Percent-encodes a string for use in a URL, leaving only unreserved characters as they are.
+///
+/// This is the encoding AWS Signature Version 4 requires for object keys and query parameters,
+/// and it is safe for any path segment or query value sent to the Docker Engine API.
+pub fn uri_encode(value: &str) -> String {
+    value.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_encode_keeps_unreserved_characters_only() {
+        assert_eq!(uri_encode("AZaz09-_.~"), "AZaz09-_.~");
+        assert_eq!(uri_encode("my volume/+=&"), "my%20volume%2F%2B%3D%26");
+        assert_eq!(uri_encode("{\"label\":[\"a=b\"]}"), "%7B%22label%22%3A%5B%22a%3Db%22%5D%7D");
+        assert_eq!(uri_encode("é"), "%C3%A9");
+    }
+}